@@ -0,0 +1,432 @@
+//! Strategies which pick the next command, from random moves to expectimax search.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_pcg::Pcg32;
+
+use crate::bitboard::Board;
+use crate::game::{self, slide, Command, Grid, Rule, Scale, SpawnMode, SpawnPolicy};
+
+/// a way to pick the next command
+pub trait Strategy {
+    /// next command for the grid, `None` if nothing can move
+    fn next_move(&mut self, grid: &Grid) -> Option<Command>;
+}
+
+/// built-in strategies
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// any legal move
+    Random,
+    /// keep big boxes in a corner
+    Corner,
+    /// expectimax search
+    Expectimax,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Random => write!(f, "random"),
+            Kind::Corner => write!(f, "corner"),
+            Kind::Expectimax => write!(f, "expectimax"),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    /// `random`, `corner` or `expectimax`
+    fn from_str(s: &str) -> Result<Kind, String> {
+        match s {
+            "random" => Ok(Kind::Random),
+            "corner" => Ok(Kind::Corner),
+            "expectimax" => Ok(Kind::Expectimax),
+            _ => Err(format!(
+                "invalid strategy `{}`, expected `random`, `corner` or `expectimax`",
+                s
+            )),
+        }
+    }
+}
+
+/// create a strategy for games made with `game`, `seed` is only used by random strategy
+pub fn strategy(
+    kind: Kind,
+    game: game::Config,
+    config: Config,
+    seed: u64,
+) -> Box<dyn Strategy + Send> {
+    match kind {
+        Kind::Random => Box::new(RandomMove::new(game.rule, seed)),
+        Kind::Corner => Box::new(Corner::new(game.rule)),
+        Kind::Expectimax => Box::new(Expectimax::new(config, game.rule, game.spawn)),
+    }
+}
+
+/// pick any legal move
+pub struct RandomMove {
+    rule: Rule,
+    rng: Pcg32,
+}
+
+impl RandomMove {
+    /// same seed picks the same moves
+    pub fn new(rule: Rule, seed: u64) -> RandomMove {
+        RandomMove {
+            rule,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomMove {
+    fn next_move(&mut self, grid: &Grid) -> Option<Command> {
+        let legal: Vec<Command> = Command::MOVES
+            .iter()
+            .copied()
+            .filter(|cmd| slide(grid, *cmd, self.rule).is_some())
+            .collect();
+
+        legal.choose(&mut self.rng).copied()
+    }
+}
+
+/// keep big boxes in the bottom-left corner, prefer down, left, right, then up
+pub struct Corner {
+    rule: Rule,
+}
+
+impl Corner {
+    /// searcher for `rule`
+    pub fn new(rule: Rule) -> Corner {
+        Corner { rule }
+    }
+}
+
+impl Strategy for Corner {
+    fn next_move(&mut self, grid: &Grid) -> Option<Command> {
+        [Command::Down, Command::Left, Command::Right, Command::Up]
+            .into_iter()
+            .find(|cmd| slide(grid, *cmd, self.rule).is_some())
+    }
+}
+
+/// search config
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// max player moves to look ahead
+    pub depth: u32,
+    /// stop deepening when time is up
+    pub time_budget: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            depth: 3,
+            time_budget: Duration::from_millis(100),
+        }
+    }
+}
+
+/// chance branches less likely than this are evaluated directly
+const MIN_PROBABILITY: f64 = 0.0001;
+
+/// Expectimax searcher
+///
+/// Rules:
+///
+/// 1. player nodes take the best of all moves
+/// 2. chance nodes average every empty box with every number the spawn policy may insert,
+///    one box per move even if the policy inserts more
+/// 3. evil and kind spawn modes take the worst or the best insertion instead of the average
+/// 4. iterative deepening, the deepest finished search wins when time is up
+pub struct Expectimax {
+    config: Config,
+    rule: Rule,
+    spawn: SpawnPolicy,
+    deadline: Instant,
+}
+
+impl Expectimax {
+    /// searcher for `rule` where boxes are inserted by `spawn`
+    pub fn new(config: Config, rule: Rule, spawn: SpawnPolicy) -> Expectimax {
+        Expectimax {
+            config,
+            rule,
+            spawn,
+            deadline: Instant::now(),
+        }
+    }
+
+    /// best command for the grid, `None` if nothing can move
+    ///
+    /// standard 4x4 grids are searched on a `Board`, everything else on the grid itself
+    pub fn best_move(&mut self, grid: &Grid) -> Option<Command> {
+        match Board::from_grid(grid) {
+            Some(board) if self.rule == Rule::Standard => self.deepen(&board),
+            _ => self.deepen(grid),
+        }
+    }
+
+    /// iterative deepening from depth 1
+    fn deepen<P: Position>(&mut self, position: &P) -> Option<Command> {
+        self.deadline = Instant::now() + self.config.time_budget;
+
+        let mut best = None;
+        for depth in 1..=self.config.depth.max(1) {
+            match self.search_root(position, depth) {
+                Some(cmd) => best = Some(cmd),
+                // time is up, keep the result of last depth
+                None => break,
+            }
+        }
+
+        // nothing finished in time, fall back to any legal move
+        best.or_else(|| {
+            Command::MOVES
+                .iter()
+                .copied()
+                .find(|cmd| position.moved(*cmd, self.rule).is_some())
+        })
+    }
+
+    /// returns `None` when time is up or nothing can move
+    fn search_root<P: Position>(&self, position: &P, depth: u32) -> Option<Command> {
+        let mut best: Option<(Command, f64)> = None;
+
+        for cmd in Command::MOVES {
+            if let Some(next) = position.moved(cmd, self.rule) {
+                let value = self.chance(&next, depth - 1, 1.0)?;
+                if best.is_none_or(|(_, v)| value > v) {
+                    best = Some((cmd, value));
+                }
+            }
+        }
+
+        best.map(|(cmd, _)| cmd)
+    }
+
+    /// best value of all moves, `depth` player moves left, `None` when time is up
+    fn player<P: Position>(&self, position: &P, depth: u32, probability: f64) -> Option<f64> {
+        if Instant::now() > self.deadline {
+            return None;
+        }
+
+        let mut best: Option<f64> = None;
+        for cmd in Command::MOVES {
+            if let Some(next) = position.moved(cmd, self.rule) {
+                let value = self.chance(&next, depth - 1, probability)?;
+                best = Some(best.map_or(value, |v| v.max(value)));
+            }
+        }
+
+        // dead grid is the worst
+        Some(best.unwrap_or(-1e9))
+    }
+
+    /// weighted average of every possible insertion, or the worst or the best one when
+    /// spawning is not random, `depth` player moves left, `None` when time is up
+    fn chance<P: Position>(&self, position: &P, depth: u32, probability: f64) -> Option<f64> {
+        if depth == 0 || probability < MIN_PROBABILITY {
+            return Some(position.value());
+        }
+
+        let empty = position.empty_cells();
+        if empty.is_empty() {
+            return self.player(position, depth, probability);
+        }
+
+        // only board-aware spawning needs the biggest box
+        let max_box = match self.spawn.scale {
+            Scale::Fixed => 0,
+            _ => position.max_box(),
+        };
+        let total: u32 = self.spawn.weights.iter().sum();
+        let mut value = 0.0;
+        let mut worst = f64::MAX;
+        let mut best = f64::MIN;
+        for &cell in &empty {
+            for (num, weight) in self.spawn.choices(max_box) {
+                let p = weight as f64 / total as f64 / empty.len() as f64;
+                let next = position.spawned(cell, num);
+                let reply = self.player(&next, depth, probability * p)?;
+                value += p * reply;
+                worst = worst.min(reply);
+                best = best.max(reply);
+            }
+        }
+
+        Some(match self.spawn.mode {
+            SpawnMode::Random => value,
+            SpawnMode::Evil => worst,
+            SpawnMode::Kind => best,
+        })
+    }
+}
+
+/// what the searcher explores, a grid or a packed board
+trait Position: Sized {
+    /// position after a move, `None` if nothing moves
+    fn moved(&self, cmd: Command, rule: Rule) -> Option<Self>;
+    /// indexes of empty boxes, `row * cols + col`
+    fn empty_cells(&self) -> Vec<usize>;
+    /// position with `num` inserted into empty box `cell`
+    fn spawned(&self, cell: usize, num: i32) -> Self;
+    /// the biggest box
+    fn max_box(&self) -> i32;
+    /// heuristic score, see `evaluate`
+    fn value(&self) -> f64;
+}
+
+impl Position for Grid {
+    fn moved(&self, cmd: Command, rule: Rule) -> Option<Grid> {
+        slide(self, cmd, rule).map(|(next, _, _)| next)
+    }
+
+    fn empty_cells(&self) -> Vec<usize> {
+        self.iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, x)| **x == 0)
+            .map(|(cell, _)| cell)
+            .collect()
+    }
+
+    fn spawned(&self, cell: usize, num: i32) -> Grid {
+        let cols = self.first().map_or(1, |row| row.len());
+        let mut next = self.clone();
+        next[cell / cols][cell % cols] = num;
+        next
+    }
+
+    fn max_box(&self) -> i32 {
+        self.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    fn value(&self) -> f64 {
+        evaluate(self)
+    }
+}
+
+/// only searched with standard rule, so `rule` is ignored
+impl Position for Board {
+    fn moved(&self, cmd: Command, _rule: Rule) -> Option<Board> {
+        self.slide(cmd).map(|(next, _, _)| next)
+    }
+
+    fn empty_cells(&self) -> Vec<usize> {
+        Board::empty_cells(*self)
+    }
+
+    fn spawned(&self, cell: usize, num: i32) -> Board {
+        self.set(cell, num.trailing_zeros() as u8)
+    }
+
+    fn max_box(&self) -> i32 {
+        match (0..16).map(|cell| self.get(cell)).max() {
+            Some(log) if log > 0 => 1 << log,
+            _ => 0,
+        }
+    }
+
+    fn value(&self) -> f64 {
+        evaluate_board(*self)
+    }
+}
+
+impl Strategy for Expectimax {
+    fn next_move(&mut self, grid: &Grid) -> Option<Command> {
+        self.best_move(grid)
+    }
+}
+
+/// heuristic score of a grid, higher is better
+///
+/// 1. more empty boxes
+/// 2. rows and columns are monotonic
+/// 3. adjacent boxes are close in value
+/// 4. the biggest box sits in a corner
+pub fn evaluate(grid: &Grid) -> f64 {
+    let rows = grid.len();
+    let cols = grid.first().map_or(0, |row| row.len());
+    let log = |x: i32| if x > 0 { (x as f64).log2() } else { 0.0 };
+
+    let empty = grid.iter().flatten().filter(|x| **x == 0).count() as f64;
+    let max = grid.iter().flatten().copied().max().unwrap_or(0);
+
+    let mut lines = 0.0;
+    for row in grid {
+        lines += line_value(&row.iter().map(|x| log(*x)).collect::<Vec<_>>());
+    }
+    for j in 0..cols {
+        lines += line_value(&grid.iter().map(|row| log(row[j])).collect::<Vec<_>>());
+    }
+
+    let corners = [
+        grid[0][0],
+        grid[0][cols - 1],
+        grid[rows - 1][0],
+        grid[rows - 1][cols - 1],
+    ];
+    let corner = if corners.contains(&max) { log(max) } else { 0.0 };
+
+    empty * 2.7 + lines + corner * 1.0
+}
+
+/// `evaluate` for a packed board, rows and columns come from a lookup table
+fn evaluate_board(board: Board) -> f64 {
+    static LINES: OnceLock<Vec<f64>> = OnceLock::new();
+    let lines = LINES.get_or_init(|| {
+        (0..=u16::MAX)
+            .map(|row| {
+                let logs: Vec<f64> = (0..4).map(|c| ((row >> (4 * c)) & 0xF) as f64).collect();
+                line_value(&logs)
+            })
+            .collect()
+    });
+
+    let transposed = board.transpose();
+    let mut value = 0.0;
+    for i in 0..4 {
+        value += lines[board.row(i) as usize] + lines[transposed.row(i) as usize];
+    }
+
+    let empty = board.empty_cells().len() as f64;
+    let max = (0..16).map(|cell| board.get(cell)).max().unwrap_or(0);
+    let corner = if [0, 3, 12, 15].iter().any(|cell| board.get(*cell) == max) {
+        max as f64
+    } else {
+        0.0
+    };
+
+    empty * 2.7 + value + corner * 1.0
+}
+
+/// monotonicity and smoothness of one row or column of log2 values, `0` is empty
+fn line_value(logs: &[f64]) -> f64 {
+    // penalty of the less monotonic direction
+    let (mut inc, mut dec) = (0.0, 0.0);
+    let mut smoothness = 0.0;
+    for pair in logs.windows(2) {
+        if pair[0] > pair[1] {
+            dec += pair[0] - pair[1];
+        } else {
+            inc += pair[1] - pair[0];
+        }
+        if pair[0] != 0.0 && pair[1] != 0.0 {
+            smoothness -= (pair[0] - pair[1]).abs();
+        }
+    }
+    let monotonicity = -f64::min(inc, dec);
+
+    monotonicity * 1.0 + smoothness * 0.1
+}
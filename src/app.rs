@@ -1,42 +1,118 @@
-use crate::game::{Game, Command, Grid};
+use std::{
+  path::PathBuf,
+  time::{Duration, Instant},
+};
+
+use crate::ai::{self, Expectimax};
+use crate::game::{Game, Command, Config, Grid, MoveOutcome, SpawnMode};
+use crate::records::{self, Record, Records};
+use crate::replay::{Replay, ReplayError};
+use crate::save::{self, SaveError};
 
 
 /// 2048 Application
 /// 
 /// Rules：
 /// 
-/// 1. make a `4 * 4` grid
-/// 2. each square is same size
-/// 3. board_size = box_size * 4
+/// 1. make a `rows * cols` grid
+/// 2. each square is same size, see `board::Board`
 /// 
-/// :> TODO make each `config` as a input list so this game can be customized;
+/// game, colours and keys are customized in `config.toml`, see `config`
 pub struct App {
-  /// terminal x-axis start point
-  pub x: f64,
-  /// terminal y-axis start point
-  pub y: f64,
-  /// each square size
-  pub box_size: f64,
   /// Application's game
   game: Game,
-  /// Application's commands
-  queue: Vec<Command>,
   /// Application's score
   score: i32,
+  /// saved game waiting for player's choice to resume
+  saved: Option<Game>,
+  /// one-off message for player, cleared by next key
+  notice: Option<String>,
+  /// high-score table and lifetime statistics
+  records: Records,
+  /// current screen
+  screen: Screen,
+  /// last time play time is counted
+  since: Instant,
+  /// AI search config
+  ai: ai::Config,
+  /// suggested command for current grid
+  hint: Option<Command>,
+  /// AI plays by itself
+  autoplay: bool,
+  /// last time AI played a move
+  autoplayed: Instant,
+  /// animate moves or not
+  animations: bool,
+  /// running animation of last move
+  animation: Option<Animation>,
+  /// watching a replay instead of playing
+  playback: Option<Playback>,
+}
+
+/// how long a move is animated
+const ANIMATION_TIME: Duration = Duration::from_millis(160);
+
+/// how often AI plays a move
+const AUTOPLAY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// replay speeds, steps per `REPLAY_INTERVAL`
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// time between two replay steps at speed `1.0`
+const REPLAY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// watching a replay
+struct Playback {
+  replay: Replay,
+  /// how many steps are played
+  position: usize,
+  paused: bool,
+  /// index into `SPEEDS`
+  speed: usize,
+  /// last time a step is played
+  stepped: Instant,
+}
+
+/// boxes moving on screen after a move
+struct Animation {
+  /// what the move did
+  outcome: MoveOutcome,
+  /// when the move was made
+  started: Instant,
+}
+
+/// what the board area shows
+#[derive(PartialEq, Clone, Copy)]
+pub enum Screen {
+  /// game board
+  Game,
+  /// high-score table and lifetime statistics
+  Stats,
 }
 
 
 impl App {
-  /// create your application
-  pub fn new() -> App {
-    let game = Game::new();
+  /// create your application, a random seed is used if `seed` is `None`
+  pub fn new(config: Config, seed: Option<u64>) -> App {
+    let game = match seed {
+      Some(seed) => Game::from_seed(config, seed),
+      None => Game::with_config(config),
+    };
     let mut app = App {
-      x: 0.0,
-      y: 0.0,
-      box_size: 40.0,
       game,
-      queue: Vec::new(),
-      score: 0
+      score: 0,
+      saved: None,
+      notice: None,
+      records: Records::default(),
+      screen: Screen::Game,
+      since: Instant::now(),
+      ai: ai::Config::default(),
+      hint: None,
+      autoplay: false,
+      autoplayed: Instant::now(),
+      animations: true,
+      animation: None,
+      playback: None,
     };
 
     // init your game
@@ -45,48 +121,409 @@ impl App {
     app
   }
 
-  /// box_size * 4
-  pub fn get_size(&self) -> f64 {
-    self.box_size * 4.0
+  /// offer to resume a saved game
+  pub fn offer_resume(&mut self, saved: Game) {
+    self.saved = Some(saved);
   }
 
-  /// calculate the next tick
-  pub fn next(&mut self) {
-    if self.is_alive() && !self.queue.is_empty() {
-      if let Some(top) = self.queue.pop() {
-        if top != Command::Nil {
-          self.game.next_tick(top);
-          self.score = self.game.get_score();
+  /// a saved game is waiting for player's choice
+  pub fn is_resume_pending(&self) -> bool {
+    self.saved.is_some()
+  }
+
+  /// continue the saved game
+  pub fn resume(&mut self) {
+    if let Some(game) = self.saved.take() {
+      self.game = game;
+      self.hint = None;
+      self.animation = None;
+      self.score = self.game.get_score();
+      self.since = Instant::now();
+    }
+  }
+
+  /// ignore the saved game and play a new one
+  pub fn discard_resume(&mut self) {
+    self.saved = None;
+  }
+
+  /// write current game into save file, a finished game is recorded and removes the save file
+  pub fn save(&mut self) -> Result<(), SaveError> {
+    self.count_play_time();
+
+    if self.is_replay() {
+      // a replay is not player's game
+      Ok(())
+    } else if self.is_resume_pending() {
+      // keep the saved game untouched, player has not decided yet
+      Ok(())
+    } else if self.is_alive() {
+      save::save(&self.game)
+    } else {
+      self.finish_game()?;
+      save::remove()
+    }
+  }
+
+  /// use loaded records
+  pub fn set_records(&mut self, records: Records) {
+    self.records = records;
+  }
+
+  /// high-score table and lifetime statistics
+  pub fn get_records(&self) -> &Records {
+    &self.records
+  }
+
+  /// current screen
+  pub fn get_screen(&self) -> Screen {
+    self.screen
+  }
+
+  /// switch between game board and statistics
+  pub fn toggle_stats(&mut self) {
+    self.count_play_time();
+    self.screen = match self.screen {
+      Screen::Game => Screen::Stats,
+      Screen::Stats => Screen::Game,
+    };
+  }
+
+  /// count play time since last call, time in modals or statistics is not counted
+  fn count_play_time(&mut self) {
+    let now = Instant::now();
+    let playing = self.screen == Screen::Game
+      && !self.is_replay()
+      && self.is_alive()
+      && !self.is_won()
+      && !self.is_resume_pending();
+    if playing {
+      self.game.add_play_time(now - self.since);
+    }
+    self.since = now;
+  }
+
+  /// record current game into high-score table and lifetime statistics, and save its replay
+  ///
+  /// returns: path of the replay file
+  fn finish_game(&mut self) -> Result<PathBuf, SaveError> {
+    let record = Record::from_game(&self.game);
+    let date = record.date;
+    self.records.add(record);
+    records::save(&self.records)?;
+    save::save_replay(&self.game.get_replay(), self.game.get_score(), date)
+  }
+
+  /// show a one-off message
+  pub fn set_notice(&mut self, notice: String) {
+    self.notice = Some(notice);
+  }
+
+  /// get the one-off message
+  pub fn get_notice(&self) -> Option<&str> {
+    self.notice.as_deref()
+  }
+
+  /// clear the one-off message
+  pub fn clear_notice(&mut self) {
+    self.notice = None;
+  }
+
+  /// use customized AI search config
+  pub fn set_ai_config(&mut self, config: ai::Config) {
+    self.ai = config;
+  }
+
+  /// AI suggested command for current grid
+  fn suggest(&self) -> Option<Command> {
+    let config = self.game.get_config();
+    Expectimax::new(self.ai, config.rule, config.spawn).best_move(&self.game.get_grid())
+  }
+
+  /// ask AI for a hint
+  pub fn hint(&mut self) {
+    if self.is_alive() && !self.is_won() {
+      self.hint = self.suggest();
+    }
+  }
+
+  /// get the hint for current grid
+  pub fn get_hint(&self) -> Option<Command> {
+    self.hint
+  }
+
+  /// let AI play or stop it
+  pub fn toggle_autoplay(&mut self) {
+    self.autoplay = !self.autoplay;
+  }
+
+  /// AI is playing
+  pub fn is_autoplay(&self) -> bool {
+    self.autoplay
+  }
+
+  /// calculate the next tick, only timers, animations, autoplay and replays,
+  /// player's moves never wait for it
+  ///
+  /// returns: the screen needs to be drawn again
+  pub fn next(&mut self) -> bool {
+    self.count_play_time();
+    let mut changed = false;
+    if let Some(playback) = &self.playback {
+      let interval = REPLAY_INTERVAL.div_f64(SPEEDS[playback.speed]);
+      if !playback.paused && playback.stepped.elapsed() >= interval {
+        self.play_step();
+        changed = true;
+      }
+    }
+    if self.autoplay {
+      if self.is_alive() && !self.is_won() && !self.is_resume_pending() {
+        if self.autoplayed.elapsed() >= AUTOPLAY_INTERVAL {
+          self.autoplayed = Instant::now();
+          if let Some(cmd) = self.suggest() {
+            self.add_command(cmd);
+            changed = true;
+          }
         }
       } else {
-        println!("queue error");
+        // game is over or waiting for player's choice
+        self.autoplay = false;
+        changed = true;
+      }
+    }
+    if let Some(animation) = &self.animation {
+      if animation.started.elapsed() >= ANIMATION_TIME {
+        self.animation = None;
+      }
+      changed = true;
+    }
+
+    changed
+  }
+
+  /// watch a replay instead of playing
+  pub fn watch(&mut self, replay: Replay) -> Result<(), ReplayError> {
+    self.game = replay.new_game()?;
+    self.score = self.game.get_score();
+    self.hint = None;
+    self.animation = None;
+    self.autoplay = false;
+    self.saved = None;
+    self.playback = Some(Playback {
+      replay,
+      position: 0,
+      paused: false,
+      speed: SPEEDS.iter().position(|s| *s == 1.0).unwrap_or(0),
+      stepped: Instant::now(),
+    });
+
+    Ok(())
+  }
+
+  /// watching a replay
+  pub fn is_replay(&self) -> bool {
+    self.playback.is_some()
+  }
+
+  /// replay progress, `(steps played, all steps, speed, paused)`
+  pub fn get_playback(&self) -> Option<(usize, usize, f64, bool)> {
+    self.playback.as_ref().map(|p| {
+      (p.position, p.replay.steps.len(), SPEEDS[p.speed], p.paused)
+    })
+  }
+
+  /// pause or continue the replay
+  pub fn toggle_pause(&mut self) {
+    if let Some(playback) = &mut self.playback {
+      playback.paused = !playback.paused;
+      playback.stepped = Instant::now();
+    }
+  }
+
+  /// replay one step forward and pause
+  pub fn step_forward(&mut self) {
+    if let Some(playback) = &mut self.playback {
+      playback.paused = true;
+    }
+    self.play_step();
+  }
+
+  /// take back one replay step and pause
+  pub fn step_back(&mut self) {
+    if let Some(playback) = &mut self.playback {
+      if playback.position == 0 {
+        return;
+      }
+      playback.paused = true;
+      // undo may be limited or disabled, play again from the start instead
+      match playback.replay.game_at(playback.position - 1) {
+        Ok(game) => {
+          playback.position -= 1;
+          self.game = game;
+          self.score = self.game.get_score();
+          self.animation = None;
+        }
+        Err(err) => self.notice = Some(err.to_string()),
       }
     }
   }
 
+  /// play replay steps faster
+  pub fn faster(&mut self) {
+    if let Some(playback) = &mut self.playback {
+      playback.speed = (playback.speed + 1).min(SPEEDS.len() - 1);
+    }
+  }
+
+  /// play replay steps slower
+  pub fn slower(&mut self) {
+    if let Some(playback) = &mut self.playback {
+      playback.speed = playback.speed.saturating_sub(1);
+    }
+  }
+
+  /// play next replay step, pause at the end
+  fn play_step(&mut self) {
+    let playback = match &mut self.playback {
+      Some(playback) => playback,
+      None => return,
+    };
+    playback.stepped = Instant::now();
+    if playback.position >= playback.replay.steps.len() {
+      playback.paused = true;
+      return;
+    }
+
+    match playback.replay.apply(&mut self.game, playback.position) {
+      Ok(outcome) => {
+        playback.position += 1;
+        self.score = self.game.get_score();
+        self.animate(outcome);
+      }
+      Err(err) => {
+        playback.paused = true;
+        self.notice = Some(err.to_string());
+      }
+    }
+  }
+
+  /// start the animation of a move
+  fn animate(&mut self, outcome: MoveOutcome) {
+    self.animation = match self.animations && outcome.changed {
+      true => Some(Animation {
+        outcome,
+        started: Instant::now(),
+      }),
+      false => None,
+    };
+  }
+
+  /// turn move animations on or off
+  pub fn set_animations(&mut self, on: bool) {
+    self.animations = on;
+    self.animation = None;
+  }
+
+  /// running animation, what last move did and progress from `0.0` to `1.0`
+  pub fn get_animation(&self) -> Option<(&MoveOutcome, f64)> {
+    self.animation.as_ref().map(|animation| {
+      let progress = animation.started.elapsed().as_secs_f64() / ANIMATION_TIME.as_secs_f64();
+      (&animation.outcome, progress.min(1.0))
+    })
+  }
+
   /// get current score
   pub fn get_score(&self) -> i32 {
     self.score
   }
 
+  /// get random seed of current game
+  pub fn get_seed(&self) -> u64 {
+    self.game.get_seed()
+  }
+
+  /// get who picks new boxes in current game
+  pub fn get_spawn_mode(&self) -> SpawnMode {
+    self.game.get_config().spawn.mode
+  }
+
+  /// get sum of all boxes
+  pub fn get_tile_sum(&self) -> i32 {
+    self.game.get_tile_sum()
+  }
+
+  /// get how many moves changed the grid
+  pub fn get_moves(&self) -> u32 {
+    self.game.get_moves()
+  }
+
+  /// get how many times undo is used
+  pub fn get_undos(&self) -> u32 {
+    self.game.get_undos()
+  }
+
+  /// take back last move
+  pub fn undo(&mut self) {
+    if self.game.undo() {
+      self.animation = None;
+      self.hint = None;
+      self.score = self.game.get_score();
+    }
+  }
+
+  /// redo last undone move
+  pub fn redo(&mut self) {
+    if self.game.redo() {
+      self.animation = None;
+      self.hint = None;
+      self.score = self.game.get_score();
+    }
+  }
+
   /// get game status, alive or dead
   pub fn is_alive(&self) -> bool {
-    self.game.alive
+    self.game.is_alive()
+  }
+
+  /// get game status, target box is reached and waiting for player's choice
+  pub fn is_won(&self) -> bool {
+    self.game.is_won()
+  }
+
+  /// keep playing after winning, there is no target any more
+  pub fn keep_going(&mut self) {
+    if self.is_won() {
+      self.game.keep_going();
+    }
   }
 
-  /// add some command
+  /// apply a command right away
   pub fn add_command(&mut self, cmd: Command) {
-    if self.is_alive() {
-      self.queue.insert(0, cmd)
+    let playing = self.is_alive() && !self.is_won() && !self.is_resume_pending() && !self.is_replay();
+    if playing && cmd != Command::Nil {
+      self.hint = None;
+      // movements are only needed to animate them
+      let outcome = match self.animations {
+        true => self.game.next_tick(cmd),
+        false => self.game.next_tick_untracked(cmd),
+      };
+      self.animate(outcome);
+      self.score = self.game.get_score();
     }
   }
 
   /// restart application
   pub fn restart(&mut self) {
-    if !self.is_alive() {
-      self.game = Game::new();
+    if !self.is_alive() || self.is_won() {
+      self.count_play_time();
+      match self.finish_game() {
+        Ok(path) => self.set_notice(format!("replay saved to {}", path.display())),
+        Err(err) => self.set_notice(format!("failed to save records, {}", err)),
+      }
+      self.game = Game::with_config(self.game.get_config());
       self.game.start();
-      self.queue = vec![];
+      self.hint = None;
+      self.animation = None;
       self.score = 0;
     }
   }
@@ -96,36 +533,5 @@ impl App {
     self.game.get_grid()
   }
 
-  /// calculate result board points when game over
-  pub fn get_game_over_modal(&self) -> Vec<(f64, f64)> {
-    let mut all: Vec<(f64, f64)> = vec![];
-    let board_size = self.get_size();
-
-    let x = board_size / 2.0 - self.box_size * 1.5;
-    let y = board_size / 2.0 - self.box_size / 2.0;
-    let width = self.box_size * 3.0;
-    let height = self.box_size - 10.0;
-    let mut p_x = x;
-    loop {
-        let i = p_x + 1.0;
-        if i >= x + width {
-            break;
-        }
-        let mut p_y = y;
-        loop {
-            let j = p_y + 1.0;
-            if j >= y + height {
-                break;
-            }
-            all.push((i, j));
-            p_y = j;
-        }
-
-        p_x = i;
-    }
-
-    all
-  }
-
 }
 
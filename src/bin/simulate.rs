@@ -0,0 +1,31 @@
+//! Headless batch simulation, compare strategies and rules without the TUI.
+//!
+//! `cargo run --release --bin simulate -- --games 1000 --strategy expectimax --json`
+
+use std::{env, process};
+
+use tui_2048::sim;
+
+fn main() {
+    let (config, json) = match sim::parse_args(env::args().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}\n{}", err, sim::USAGE);
+            process::exit(2);
+        }
+    };
+
+    let report = sim::run(&config);
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+    } else {
+        print!("{}", report);
+    }
+}
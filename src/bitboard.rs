@@ -0,0 +1,260 @@
+//! Packed 4x4 board with precomputed row moves, the fast path of the standard rule.
+
+use std::sync::OnceLock;
+
+use crate::game::{slide_lines, Command, Grid, Rule};
+
+/// biggest box a board accepts, so one more merge still fits in 4 bits
+pub const MAX_BOX: i32 = 16384;
+
+/// packed 4x4 board for the standard rule
+///
+/// Rules:
+///
+/// 1. each box takes 4 bits holding log2 of its value, `0` is an empty box
+/// 2. box `(row, col)` sits at bits `4 * (row * 4 + col)`, so each row is 16 bits
+/// 3. rows move through precomputed lookup tables, columns are transposed into rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Board(pub u64);
+
+/// row lookup tables, indexed by a 16-bit row
+struct Tables {
+    /// row moved towards column 0
+    left: Vec<u16>,
+    /// row moved towards column 3
+    right: Vec<u16>,
+    /// merge points of moving a row left, `points[reverse_row(row)]` for right
+    points: Vec<u32>,
+    /// biggest box merged by moving a row left, `0` when nothing merges, mirrored like `points`
+    biggest: Vec<u32>,
+}
+
+/// tables are built once, from the line engine so both engines always agree
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+
+    TABLES.get_or_init(|| {
+        let mut left = vec![0; 1 << 16];
+        let mut points = vec![0; 1 << 16];
+        let mut biggest = vec![0; 1 << 16];
+        for row in 0..=u16::MAX {
+            let line = vec![(0..4).map(|c| to_value(nibble(row as u64, c))).collect()];
+            let (moved, gained, merged) = slide_lines(&line, Command::Left, Rule::Standard)
+                .unwrap_or((line, 0, 0));
+            // a merge above 32768 needs 5 bits, inputs are capped by `MAX_BOX` so it never happens
+            left[row as usize] = moved[0]
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (c, x)| acc | (to_log(*x).min(15) as u16) << (4 * c));
+            points[row as usize] = gained as u32;
+            biggest[row as usize] = merged as u32;
+        }
+
+        let right = (0..=u16::MAX)
+            .map(|row| reverse_row(left[reverse_row(row) as usize]))
+            .collect();
+
+        Tables {
+            left,
+            right,
+            points,
+            biggest,
+        }
+    })
+}
+
+impl Board {
+    /// pack a grid, `None` unless it is 4x4 and every box is a power of two up to `MAX_BOX`
+    pub fn from_grid(grid: &Grid) -> Option<Board> {
+        if grid.len() != 4 || grid.iter().any(|row| row.len() != 4) {
+            return None;
+        }
+
+        let mut board = 0;
+        for (i, row) in grid.iter().enumerate() {
+            for (j, x) in row.iter().enumerate() {
+                let valid = *x == 0 || (*x >= 2 && *x <= MAX_BOX && x.count_ones() == 1);
+                if !valid {
+                    return None;
+                }
+                board |= (to_log(*x) as u64) << (4 * (i * 4 + j));
+            }
+        }
+
+        Some(Board(board))
+    }
+
+    /// unpack into a grid
+    pub fn to_grid(self) -> Grid {
+        (0..4)
+            .map(|i| (0..4).map(|j| to_value(self.get(i * 4 + j))).collect())
+            .collect()
+    }
+
+    /// log2 of box `cell`, `cell = row * 4 + col`
+    pub fn get(self, cell: usize) -> u8 {
+        nibble(self.0, cell)
+    }
+
+    /// put log2 value `log` into box `cell`
+    pub fn set(self, cell: usize, log: u8) -> Board {
+        let shift = 4 * cell;
+        Board((self.0 & !(0xF << shift)) | ((log as u64 & 0xF) << shift))
+    }
+
+    /// indexes of empty boxes
+    pub fn empty_cells(self) -> Vec<usize> {
+        (0..16).filter(|cell| self.get(*cell) == 0).collect()
+    }
+
+    /// 16-bit row `i`
+    pub fn row(self, i: usize) -> u16 {
+        (self.0 >> (16 * i)) as u16
+    }
+
+    /// swap rows and columns
+    pub fn transpose(self) -> Board {
+        let x = self.0;
+        let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+        let a2 = x & 0x0000_F0F0_0000_F0F0;
+        let a3 = x & 0x0F0F_0000_0F0F_0000;
+        let a = a1 | (a2 << 12) | (a3 >> 12);
+        let b1 = a & 0xFF00_FF00_00FF_00FF;
+        let b2 = a & 0x00FF_00FF_0000_0000;
+        let b3 = a & 0x0000_0000_FF00_FF00;
+
+        Board(b1 | (b2 >> 24) | (b3 << 24))
+    }
+
+    /// move boxes with standard rule
+    ///
+    /// returns: `Some((board, points, biggest merged box))` means value changes,
+    /// `None` means no change
+    pub fn slide(self, cmd: Command) -> Option<(Board, u32, u32)> {
+        let tables = tables();
+        let left = |row: u16| (tables.points[row as usize], tables.biggest[row as usize]);
+        let right = |row: u16| left(reverse_row(row));
+        let (next, points, biggest) = match cmd {
+            Command::Left => self.slide_rows(&tables.left, left),
+            Command::Right => self.slide_rows(&tables.right, right),
+            Command::Up => {
+                let (moved, points, biggest) = self.transpose().slide_rows(&tables.left, left);
+                (moved.transpose(), points, biggest)
+            }
+            Command::Down => {
+                let (moved, points, biggest) = self.transpose().slide_rows(&tables.right, right);
+                (moved.transpose(), points, biggest)
+            }
+            Command::Nil => (self, 0, 0),
+        };
+
+        if next == self {
+            None
+        } else {
+            Some((next, points, biggest))
+        }
+    }
+
+    /// move every row through a table, `merges_of` gives a row's points and biggest merge
+    fn slide_rows<F>(self, table: &[u16], merges_of: F) -> (Board, u32, u32)
+    where
+        F: Fn(u16) -> (u32, u32),
+    {
+        (0..4).fold((Board(0), 0, 0), |(board, points, biggest), i| {
+            let row = self.row(i);
+            let (row_points, row_biggest) = merges_of(row);
+            (
+                Board(board.0 | (table[row as usize] as u64) << (16 * i)),
+                points + row_points,
+                biggest.max(row_biggest),
+            )
+        })
+    }
+}
+
+/// 4 bits at `cell`
+fn nibble(bits: u64, cell: usize) -> u8 {
+    ((bits >> (4 * cell)) & 0xF) as u8
+}
+
+/// mirror a row, column 0 <-> column 3
+fn reverse_row(row: u16) -> u16 {
+    (row >> 12) | ((row >> 4) & 0x00F0) | ((row << 4) & 0x0F00) | (row << 12)
+}
+
+/// box value -> log2, `0` stays `0`
+fn to_log(x: i32) -> u8 {
+    if x == 0 {
+        0
+    } else {
+        x.trailing_zeros() as u8
+    }
+}
+
+/// log2 -> box value, `0` stays `0`
+fn to_value(log: u8) -> i32 {
+    if log == 0 {
+        0
+    } else {
+        1 << log
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Config, Game};
+    use rand::{Rng, SeedableRng};
+    use rand_pcg::Pcg32;
+
+    #[test]
+    fn grid_round_trip() {
+        let grid = vec![
+            vec![0, 2, 4, 8],
+            vec![16, 32, 64, 128],
+            vec![256, 512, 1024, 2048],
+            vec![4096, 8192, 16384, 0],
+        ];
+        let board = Board::from_grid(&grid).unwrap();
+
+        assert_eq!(board.to_grid(), grid);
+        assert_eq!(board.transpose().transpose(), board);
+        assert_eq!(board.transpose().to_grid()[1][0], 2);
+        assert!(Board::from_grid(&vec![vec![32768, 0, 0, 0]; 4]).is_none());
+        assert!(Board::from_grid(&vec![vec![0; 5]; 4]).is_none());
+    }
+
+    /// play random games, every move of every position must match the line engine
+    #[test]
+    fn matches_line_engine_move_for_move() {
+        let mut rng = Pcg32::seed_from_u64(2048);
+
+        for seed in 0..200 {
+            let config = Config {
+                undo_limit: 0,
+                ..Config::default()
+            };
+            let mut game = Game::from_seed(config, seed);
+            game.start();
+
+            while game.is_alive() {
+                let grid = game.get_grid();
+                let board = Board::from_grid(&grid).unwrap();
+                for cmd in Command::MOVES {
+                    let expected = slide_lines(&grid, cmd, Rule::Standard);
+                    let actual = board
+                        .slide(cmd)
+                        .map(|(next, points, biggest)| {
+                            (next.to_grid(), points as i32, biggest as i32)
+                        });
+                    assert_eq!(actual, expected, "seed {}, {:?} on {:?}", seed, cmd, grid);
+                }
+
+                if game.is_won() {
+                    game.keep_going();
+                }
+                game.next_tick(Command::MOVES[rng.gen_range(0..4)]);
+            }
+        }
+    }
+}
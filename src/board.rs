@@ -0,0 +1,434 @@
+use tui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, BorderType, Widget},
+};
+
+use tui_2048::game::{Grid, MoveOutcome};
+
+use crate::theme::{Theme, Tile};
+
+/// share of an animation spent sliding, the rest pops merged boxes and fades in new boxes
+const SLIDE_PART: f64 = 0.6;
+
+/// tiles are twice as many cells wide as tall, terminal cells are about twice as tall as wide
+const ASPECT: u16 = 2;
+
+/// game board drawn on terminal cells
+///
+/// Rules:
+///
+/// 1. tiles fill the area, one cell of grid line between them, and stay roughly square
+/// 2. numbers are centred in their tile, the tile background is the box colour
+/// 3. the board is centred in the area and recomputed every frame, so it reflows on resize
+pub struct Board<'a> {
+    grid: &'a Grid,
+    theme: &'a Theme,
+    animation: Option<(&'a MoveOutcome, f64)>,
+    block: Option<Block<'a>>,
+    /// message and choices drawn in a frame over the board
+    modal: Option<(&'a str, &'a str, Color)>,
+    /// grid lines are `+`, `-` and `|`
+    ascii: bool,
+}
+
+impl<'a> Board<'a> {
+    pub fn new(grid: &'a Grid, theme: &'a Theme) -> Board<'a> {
+        Board {
+            grid,
+            theme,
+            animation: None,
+            block: None,
+            modal: None,
+            ascii: false,
+        }
+    }
+
+    /// running animation of the last move and its progress from `0.0` to `1.0`
+    pub fn animation(mut self, animation: Option<(&'a MoveOutcome, f64)>) -> Board<'a> {
+        self.animation = animation;
+        self
+    }
+
+    pub fn block(mut self, block: Block<'a>) -> Board<'a> {
+        self.block = Some(block);
+        self
+    }
+
+    /// e.g. `GAME OVER!` with `Restart[R] Quit[Q]`, framed in `color`
+    pub fn modal(mut self, modal: Option<(&'a str, &'a str, Color)>) -> Board<'a> {
+        self.modal = modal;
+        self
+    }
+
+    pub fn ascii(mut self, ascii: bool) -> Board<'a> {
+        self.ascii = ascii;
+        self
+    }
+
+    /// tile width and height fitting `area`, `None` if the widest number does not fit
+    fn tile_size(&self, area: Rect) -> Option<(u16, u16)> {
+        let rows = self.grid.len() as u16;
+        let cols = self.grid.first().map_or(0, |row| row.len()) as u16;
+        if rows == 0 || cols == 0 || area.width <= cols || area.height <= rows {
+            return None;
+        }
+        let digits = self
+            .grid
+            .iter()
+            .flatten()
+            .map(|x| x.to_string().len() as u16)
+            .max()
+            .unwrap_or(1);
+
+        let width = (area.width - cols - 1) / cols;
+        let height = (area.height - rows - 1) / rows;
+        if width < digits || height == 0 {
+            return None;
+        }
+        // keep tiles square, but never narrower than the numbers with a margin
+        let width = width.min((height * ASPECT).max(digits + 2));
+        let height = height.min((width / ASPECT).max(1));
+
+        Some((width, height))
+    }
+
+    fn line_symbols(&self) -> [&'static str; 2] {
+        if self.ascii {
+            return ["-", "|"];
+        }
+        let set = BorderType::line_symbols(self.theme.border_type);
+        [set.horizontal, set.vertical]
+    }
+
+    /// where grid lines cross, `up`, `down`, `left` and `right` tell which lines meet there
+    fn junction(&self, up: bool, down: bool, left: bool, right: bool) -> &'static str {
+        if self.ascii {
+            return "+";
+        }
+        let set = BorderType::line_symbols(self.theme.border_type);
+        match (up, down, left, right) {
+            (false, true, false, true) => set.top_left,
+            (false, true, true, false) => set.top_right,
+            (true, false, false, true) => set.bottom_left,
+            (true, false, true, false) => set.bottom_right,
+            (false, true, true, true) => set.horizontal_down,
+            (true, false, true, true) => set.horizontal_up,
+            (true, true, false, true) => set.vertical_right,
+            (true, true, true, false) => set.vertical_left,
+            _ => set.cross,
+        }
+    }
+
+    /// fill, label and text weight of a box `width` cells wide
+    ///
+    /// with colours, boxes are blank and numbers bold. Without, boxes are told apart by tier:
+    /// `2` to `4` plain, `8` to `32` dotted, then shaded denser and denser, bold from `64` on,
+    /// `(512)` and `[2048]` in brackets
+    fn mark(&self, value: i32, width: u16) -> (&'static str, String, Modifier) {
+        if !self.theme.marks {
+            return (" ", value.to_string(), Modifier::BOLD);
+        }
+        let tier = match value {
+            i32::MIN..=4 => 0,
+            5..=32 => 1,
+            33..=256 => 2,
+            257..=1024 => 3,
+            _ => 4,
+        };
+        let fills = if self.ascii {
+            [" ", ".", ":", "+", "#"]
+        } else {
+            [" ", "·", "░", "▒", "▓"]
+        };
+        let (open, close) = [("", ""), ("", ""), ("", ""), ("(", ")"), ("[", "]")][tier];
+        let modifier = if tier >= 2 { Modifier::BOLD } else { Modifier::empty() };
+
+        // a blank margin keeps the number readable over the fill, if it fits
+        let label = [
+            format!(" {}{}{} ", open, value, close),
+            format!("{}{}{}", open, value, close),
+        ]
+        .into_iter()
+        .find(|label| label.len() <= width as usize)
+        .unwrap_or_else(|| value.to_string());
+
+        (fills[tier], label, modifier)
+    }
+
+    /// frame of cell `(row, col)` starting at `(x, y)`
+    fn draw_cell_lines(
+        &self,
+        buf: &mut Buffer,
+        (x, y): (u16, u16),
+        (w, h): (u16, u16),
+        cell: (usize, usize),
+        color: Color,
+    ) {
+        let rows = self.grid.len();
+        let cols = self.grid[0].len();
+        let [horizontal, vertical] = self.line_symbols();
+        let style = Style::default().fg(color);
+
+        for i in 1..=w {
+            buf.get_mut(x + i, y)
+                .set_symbol(horizontal)
+                .set_style(style);
+            buf.get_mut(x + i, y + h + 1)
+                .set_symbol(horizontal)
+                .set_style(style);
+        }
+        for j in 1..=h {
+            buf.get_mut(x, y + j).set_symbol(vertical).set_style(style);
+            buf.get_mut(x + w + 1, y + j)
+                .set_symbol(vertical)
+                .set_style(style);
+        }
+        let (row, col) = cell;
+        for (dy, dx, r, c) in [
+            (0, 0, row, col),
+            (0, w + 1, row, col + 1),
+            (h + 1, 0, row + 1, col),
+            (h + 1, w + 1, row + 1, col + 1),
+        ] {
+            // `r` and `c` index grid lines, `0..=rows` and `0..=cols`
+            let symbol = self.junction(r > 0, r < rows, c > 0, c < cols);
+            buf.get_mut(x + dx, y + dy)
+                .set_symbol(symbol)
+                .set_style(style);
+        }
+    }
+}
+
+impl<'a> Widget for Board<'a> {
+    fn render(mut self, area: Rect, buf: &mut Buffer) {
+        let area = match self.block.take() {
+            Some(block) => {
+                let inner = block.inner(area);
+                block.render(area, buf);
+                inner
+            }
+            None => area,
+        };
+
+        let (width, height) = match self.tile_size(area) {
+            Some(size) => size,
+            None => {
+                let text = "enlarge the terminal";
+                let x = area.x + area.width.saturating_sub(text.len() as u16) / 2;
+                let y = area.y + area.height / 2;
+                buf.set_stringn(
+                    x,
+                    y,
+                    text,
+                    area.width as usize,
+                    Style::default().fg(self.theme.text),
+                );
+                return;
+            }
+        };
+        let rows = self.grid.len() as u16;
+        let cols = self.grid[0].len() as u16;
+        let board_width = cols * (width + 1) + 1;
+        let board_height = rows * (height + 1) + 1;
+        let x0 = area.x + (area.width - board_width) / 2;
+        let y0 = area.y + (area.height - board_height) / 2;
+        let cell_origin = |row: usize, col: usize| {
+            (
+                x0 + col as u16 * (width + 1),
+                y0 + row as u16 * (height + 1),
+            )
+        };
+
+        let (boxes, borders) = frame_boxes(self.grid, self.animation, self.theme);
+        // highlighted frames last, so their shared lines keep the highlight
+        let (plain, highlighted): (Vec<_>, Vec<_>) = borders
+            .into_iter()
+            .partition(|(_, color)| *color == self.theme.border);
+        for ((row, col), color) in plain.into_iter().chain(highlighted) {
+            self.draw_cell_lines(
+                buf,
+                cell_origin(row, col),
+                (width, height),
+                (row, col),
+                color,
+            );
+        }
+
+        for (row, col, value, tile) in boxes {
+            // fractional while sliding, rounded to the nearest terminal cell
+            let x = x0 + 1 + (col * (width + 1) as f64).round() as u16;
+            let y = y0 + 1 + (row * (height + 1) as f64).round() as u16;
+            let cells = Rect::new(x, y, width, height).intersection(area);
+            let (fill, label, modifier) = self.mark(value, width);
+            for j in cells.top()..cells.bottom() {
+                for i in cells.left()..cells.right() {
+                    buf.get_mut(i, j)
+                        .set_symbol(fill)
+                        .set_fg(tile.fg)
+                        .set_bg(tile.bg);
+                }
+            }
+            let style = Style::default()
+                .fg(tile.fg)
+                .bg(tile.bg)
+                .add_modifier(modifier);
+            buf.set_stringn(
+                x + width.saturating_sub(label.len() as u16) / 2,
+                y + height / 2,
+                &label,
+                width as usize,
+                style,
+            );
+        }
+
+        if let Some((message, choices, color)) = self.modal {
+            let modal_width = message.len().max(choices.len()) as u16 + 4;
+            let modal = Rect::new(
+                (x0 + board_width / 2).saturating_sub(modal_width / 2),
+                (y0 + board_height / 2).saturating_sub(2),
+                modal_width,
+                5,
+            )
+            .intersection(area);
+            let [horizontal, vertical] = self.line_symbols();
+            let frame = Style::default().fg(color);
+            for j in modal.top()..modal.bottom() {
+                for i in modal.left()..modal.right() {
+                    let symbol = match (
+                        j == modal.top() || j + 1 == modal.bottom(),
+                        i == modal.left() || i + 1 == modal.right(),
+                    ) {
+                        (true, true) => self.junction(
+                            j != modal.top(),
+                            j == modal.top(),
+                            i != modal.left(),
+                            i == modal.left(),
+                        ),
+                        (true, false) => horizontal,
+                        (false, true) => vertical,
+                        (false, false) => " ",
+                    };
+                    buf.get_mut(i, j)
+                        .set_symbol(symbol)
+                        .set_style(Style::reset().patch(frame));
+                }
+            }
+            let text = Style::default().fg(self.theme.text);
+            for (dy, line) in [(1, message), (3, choices)] {
+                let x = modal.x + modal.width.saturating_sub(line.len() as u16) / 2;
+                if modal.y + dy < modal.bottom() {
+                    buf.set_stringn(x, modal.y + dy, line, modal.width as usize, text);
+                }
+            }
+        }
+    }
+}
+
+/// what to draw for this frame
+///
+/// returns: boxes as `(row, col, value, tile)`, `row` and `col` are fractional while sliding,
+/// and box borders as `((row, col), color)`
+#[allow(clippy::type_complexity)]
+fn frame_boxes(
+    grid: &Grid,
+    animation: Option<(&MoveOutcome, f64)>,
+    theme: &Theme,
+) -> (Vec<(f64, f64, i32, Tile)>, Vec<((usize, usize), Color)>) {
+    let mut borders: Vec<((usize, usize), Color)> = grid
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| (0..row.len()).map(move |j| ((i, j), theme.border)))
+        .collect();
+
+    let (outcome, progress) = match animation {
+        Some(animation) => animation,
+        None => {
+            let boxes = grid
+                .iter()
+                .enumerate()
+                .flat_map(|(i, row)| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|(_, x)| **x != 0)
+                        .map(move |(j, x)| (i as f64, j as f64, *x, theme.tile(*x)))
+                })
+                .collect();
+            return (boxes, borders);
+        }
+    };
+
+    // boxes slide from old cells to new cells, new boxes are not there yet
+    if progress < SLIDE_PART {
+        let t = progress / SLIDE_PART;
+        let boxes = outcome
+            .movements
+            .iter()
+            .map(|m| {
+                let row = m.from.0 as f64 + (m.to.0 as f64 - m.from.0 as f64) * t;
+                let col = m.from.1 as f64 + (m.to.1 as f64 - m.from.1 as f64) * t;
+                (row, col, m.value, theme.tile(m.value))
+            })
+            .collect();
+        return (boxes, borders);
+    }
+
+    // merged boxes pop with a bright border, new boxes fade in
+    let t = (progress - SLIDE_PART) / (1.0 - SLIDE_PART);
+    for (merged, _) in &outcome.merges {
+        if let Some(border) = borders.iter_mut().find(|(cell, _)| cell == merged) {
+            border.1 = theme.highlight;
+        }
+    }
+    let boxes = grid
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, x)| **x != 0)
+                .map(move |(j, x)| (i, j, *x))
+        })
+        .map(|(i, j, x)| {
+            let spawned = outcome.spawns.iter().any(|spawn| spawn.cell == (i, j));
+            let mut tile = theme.tile(x);
+            match spawned {
+                true if t < 0.5 => tile.bg = theme.fade[0],
+                true if t < 1.0 => tile.bg = theme.fade[1],
+                _ => {}
+            }
+            (i as f64, j as f64, x, tile)
+        })
+        .collect();
+
+    (boxes, borders)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_tell_boxes_apart_without_colour() {
+        let grid = vec![vec![0; 4]; 4];
+        let theme = Theme::default().without_color();
+        let board = Board::new(&grid, &theme);
+
+        let marks: Vec<_> = [2, 8, 64, 512, 2048]
+            .iter()
+            .map(|value| board.mark(*value, 10))
+            .collect();
+        for (i, mark) in marks.iter().enumerate() {
+            assert!(marks[i + 1..].iter().all(|other| other != mark));
+        }
+        assert_eq!(marks[4], ("▓", String::from(" [2048] "), Modifier::BOLD));
+        // brackets and margin go before the number does
+        assert_eq!(board.mark(2048, 6).1, "[2048]");
+        assert_eq!(board.mark(2048, 4).1, "2048");
+
+        let theme = Theme::default();
+        let board = Board::new(&grid, &theme);
+        assert_eq!(board.mark(2048, 10), (" ", String::from("2048"), Modifier::BOLD));
+    }
+}
@@ -0,0 +1,235 @@
+use std::{fmt, path::PathBuf, time::Duration};
+
+use crate::ai;
+use crate::game::Config;
+use crate::sim;
+use crate::theme::{self, Theme};
+use crate::utils::{self, MAX_SIDE, MIN_SIDE};
+
+/// usage text printed on bad arguments
+pub const USAGE: &str = "\
+Usage: tui-2048 [play] [options]
+       tui-2048 replay <file> [options]
+       tui-2048 text [options]
+       tui-2048 solve [options] [--keep-going] [--out <file>]
+       tui-2048 stats [--json]
+       tui-2048 simulate [simulate options]
+
+Options: [--size <rows>x<cols>] [--rule standard|chain] [--seed <number>]
+         [--undo-limit <number>] [--target <number>] [--theme <name>]
+         [--no-color] [--ascii] [--no-animation] [--replay <file>]
+         [--ai-depth <number>] [--ai-budget <ms>]
+         [--spawn-weights <2,4,8,16>] [--spawn-per-move <number>]
+         [--spawn-initial <number>] [--spawn-scale fixed|max-box/<number>]
+         [--spawn-mode random|evil|kind]";
+
+/// what to run, the first argument, `play` if it is a flag
+#[derive(Debug)]
+pub enum Run {
+    /// play in the terminal
+    Play,
+    /// watch a replay file
+    Replay(PathBuf),
+    /// play with plain text lines on stdin and stdout, for screen readers
+    Text,
+    /// let expectimax play one game without the TUI and print it
+    Solve {
+        /// play on after the target box
+        keep_going: bool,
+        /// write the replay here
+        out: Option<PathBuf>,
+    },
+    /// print high scores and lifetime statistics
+    Stats {
+        /// the records file as JSON instead of tables
+        json: bool,
+    },
+    /// play many games without the TUI, takes the flags of the `simulate` binary
+    Simulate(sim::Config, bool),
+}
+
+/// command line options
+#[derive(Debug, Default)]
+pub struct Options {
+    /// game config
+    pub game: Config,
+    /// random seed, replay a game with same seed
+    pub seed: Option<u64>,
+    /// AI search config
+    pub ai: ai::Config,
+    /// boxes snap into place instead of sliding
+    pub no_animation: bool,
+    /// built-in or user theme replacing the one in `config.toml`
+    pub theme: Option<Theme>,
+    /// leave every colour to the terminal
+    pub no_color: bool,
+    /// plain ASCII board and labels, for terminals without Unicode
+    pub ascii: bool,
+}
+
+/// bad command line arguments, with the usage text to print
+#[derive(Debug)]
+pub struct ArgError(String, &'static str);
+
+impl ArgError {
+    fn new(reason: String) -> ArgError {
+        ArgError(reason, USAGE)
+    }
+}
+
+impl fmt::Display for ArgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n{}", self.0, self.1)
+    }
+}
+
+impl std::error::Error for ArgError {}
+
+/// parse command line arguments, program name excluded, flags override `defaults`
+///
+/// e.g. `--size 5x5`, `replay game.replay --ascii`, `stats --json`
+pub fn parse<I>(args: I, defaults: Options) -> Result<(Run, Options), ArgError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut options = defaults;
+    let mut args = args.into_iter().peekable();
+
+    let command = match args.peek() {
+        Some(arg) if !arg.starts_with('-') => args.next(),
+        _ => None,
+    };
+    let mut run = match command.as_deref() {
+        None | Some("play") => Run::Play,
+        Some("replay") => match args.next() {
+            Some(path) if !path.starts_with('-') => Run::Replay(PathBuf::from(path)),
+            _ => return Err(ArgError::new(String::from("`replay` expects a file"))),
+        },
+        Some("text") => Run::Text,
+        Some("solve") => Run::Solve {
+            keep_going: false,
+            out: None,
+        },
+        Some("stats") => Run::Stats { json: false },
+        Some("simulate") => {
+            let (config, json) =
+                sim::parse_args(args).map_err(|reason| ArgError(reason, sim::USAGE))?;
+            return Ok((Run::Simulate(config, json), options));
+        }
+        Some(other) => return Err(ArgError::new(format!("unknown command `{}`", other))),
+    };
+
+    while let Some(arg) = args.next() {
+        if let Run::Stats { json } = &mut run {
+            match arg.as_str() {
+                "--json" => *json = true,
+                _ => return Err(ArgError::new(format!("unknown argument `{}`", arg))),
+            }
+            continue;
+        }
+        match arg.as_str() {
+            "--size" => {
+                let value = next_value(&mut args, &arg)?;
+                let (rows, cols) =
+                    utils::parse_size(&value, MIN_SIDE, MAX_SIDE).map_err(ArgError::new)?;
+                options.game.rows = rows;
+                options.game.cols = cols;
+            }
+            "--rule" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.rule = value.parse().map_err(ArgError::new)?;
+            }
+            "--seed" => {
+                let value = next_value(&mut args, &arg)?;
+                let seed = value
+                    .parse()
+                    .map_err(|_| ArgError::new(format!("invalid seed `{}`", value)))?;
+                options.seed = Some(seed);
+            }
+            "--undo-limit" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.undo_limit = value
+                    .parse()
+                    .map_err(|_| ArgError::new(format!("invalid undo limit `{}`", value)))?;
+            }
+            "--target" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.target = utils::parse_target(&value).map_err(ArgError::new)?;
+            }
+            "--ai-depth" => {
+                let value = next_value(&mut args, &arg)?;
+                options.ai.depth = match value.parse() {
+                    Ok(depth) if depth > 0 => depth,
+                    _ => return Err(ArgError::new(format!("invalid AI depth `{}`", value))),
+                };
+            }
+            "--ai-budget" => {
+                let value = next_value(&mut args, &arg)?;
+                let ms = value
+                    .parse()
+                    .map_err(|_| ArgError::new(format!("invalid AI budget `{}`", value)))?;
+                options.ai.time_budget = Duration::from_millis(ms);
+            }
+            "--spawn-weights" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.spawn.weights =
+                    utils::parse_weights(&value).map_err(ArgError::new)?;
+            }
+            "--spawn-per-move" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.spawn.per_move = value
+                    .parse()
+                    .map_err(|_| ArgError::new(format!("invalid spawn count `{}`", value)))?;
+            }
+            "--spawn-initial" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.spawn.initial = value
+                    .parse()
+                    .map_err(|_| ArgError::new(format!("invalid start count `{}`", value)))?;
+            }
+            "--spawn-scale" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.spawn.scale = value.parse().map_err(ArgError::new)?;
+            }
+            "--spawn-mode" => {
+                let value = next_value(&mut args, &arg)?;
+                options.game.spawn.mode = value.parse().map_err(ArgError::new)?;
+            }
+            "--theme" => {
+                let value = next_value(&mut args, &arg)?;
+                options.theme = Some(theme::load(&value).map_err(ArgError::new)?);
+            }
+            // kept from before subcommands, same as `replay <file>`
+            "--replay" => match run {
+                Run::Play => run = Run::Replay(PathBuf::from(next_value(&mut args, &arg)?)),
+                _ => return Err(ArgError::new(String::from("`--replay` is for `play`"))),
+            },
+            "--no-color" => options.no_color = true,
+            "--ascii" => options.ascii = true,
+            "--no-animation" => options.no_animation = true,
+            "--keep-going" => match &mut run {
+                Run::Solve { keep_going, .. } => *keep_going = true,
+                _ => return Err(ArgError::new(String::from("`--keep-going` is for `solve`"))),
+            },
+            "--out" => match &mut run {
+                Run::Solve { out, .. } => {
+                    *out = Some(PathBuf::from(next_value(&mut args, &arg)?));
+                }
+                _ => return Err(ArgError::new(String::from("`--out` is for `solve`"))),
+            },
+            _ => return Err(ArgError::new(format!("unknown argument `{}`", arg))),
+        }
+    }
+    options.game.spawn.validate().map_err(ArgError::new)?;
+
+    Ok((run, options))
+}
+
+/// value following a flag
+fn next_value<I>(args: &mut I, flag: &str) -> Result<String, ArgError>
+where
+    I: Iterator<Item = String>,
+{
+    args.next()
+        .ok_or_else(|| ArgError::new(format!("`{}` expects a value", flag)))
+}
@@ -0,0 +1,553 @@
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use termion::event::Key;
+
+use crate::cli::Options;
+use crate::game::SPAWN_VALUES;
+use crate::theme::{ColorDepth, Theme, ThemeFile};
+use crate::utils::{self, MAX_SIDE, MIN_SIDE};
+
+/// config file name under the config directory
+const FILE_NAME: &str = "config.toml";
+
+/// fastest and slowest tick rate in ms
+const MIN_TICK_RATE: u64 = 10;
+const MAX_TICK_RATE: u64 = 1000;
+
+/// everything `config.toml` sets, command line flags are applied on top of `options`
+pub struct Settings {
+    /// defaults of command line options
+    pub options: Options,
+    /// animations and timers
+    pub tick_rate: Duration,
+    pub theme: Theme,
+    /// colours the terminal can show, `None` to guess
+    pub color_depth: Option<ColorDepth>,
+    pub keys: Keys,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            options: Options {
+                // https://no-color.org, `[ui] color` and flags still override it
+                no_color: matches!(env::var_os("NO_COLOR"), Some(v) if !v.is_empty()),
+                ..Options::default()
+            },
+            tick_rate: Duration::from_millis(30),
+            theme: Theme::default(),
+            color_depth: None,
+            keys: Keys::default(),
+        }
+    }
+}
+
+/// config errors
+#[derive(Debug)]
+pub enum ConfigError {
+    /// read failed
+    Io(PathBuf, io::Error),
+    /// file content is not valid, the reason names the key
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            ConfigError::Invalid(path, reason) => {
+                write!(f, "{}: invalid config, {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// `$XDG_CONFIG_HOME/tui-2048` or `~/.config/tui-2048`
+pub fn dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("tui-2048"))
+}
+
+/// `$XDG_CONFIG_HOME/tui-2048/config.toml` or `~/.config/tui-2048/config.toml`
+pub fn path() -> Option<PathBuf> {
+    dir().map(|dir| dir.join(FILE_NAME))
+}
+
+/// read config file, defaults if there is none
+pub fn load() -> Result<Settings, ConfigError> {
+    match path() {
+        Some(path) if path.exists() => load_from(&path),
+        _ => Ok(Settings::default()),
+    }
+}
+
+/// read a config file
+pub fn load_from(path: &Path) -> Result<Settings, ConfigError> {
+    let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+    let invalid = |reason: String| ConfigError::Invalid(path.to_path_buf(), reason);
+
+    let file: File = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+    file.into_settings().map_err(invalid)
+}
+
+/// layout of `config.toml`, every key is optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    game: GameSection,
+    spawn: SpawnSection,
+    ui: UiSection,
+    theme: ThemeFile,
+    keys: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct GameSection {
+    /// `<rows>x<cols>`
+    size: Option<String>,
+    /// `standard` or `chain`
+    rule: Option<String>,
+    target: Option<i32>,
+    undo_limit: Option<usize>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct SpawnSection {
+    /// weights of `2`, `4`, `8` and `16`
+    weights: Option<Vec<u32>>,
+    per_move: Option<usize>,
+    initial: Option<usize>,
+    /// `fixed` or `max-box/<n>`
+    scale: Option<String>,
+    /// `random`, `evil` or `kind`
+    mode: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct UiSection {
+    /// ms
+    tick_rate: Option<u64>,
+    animations: Option<bool>,
+    /// `false` leaves every colour to the terminal
+    color: Option<bool>,
+    /// plain ASCII board and labels
+    ascii: Option<bool>,
+    /// `auto`, `16`, `256` or `truecolor`
+    color_depth: Option<String>,
+}
+
+impl File {
+    /// check every value, errors name the key like `game.size`
+    fn into_settings(self) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+
+        let game = &mut settings.options.game;
+        if let Some(size) = self.game.size {
+            (game.rows, game.cols) =
+                utils::parse_size(&size, MIN_SIDE, MAX_SIDE).map_err(at("game.size"))?;
+        }
+        if let Some(rule) = self.game.rule {
+            game.rule = rule.parse().map_err(at("game.rule"))?;
+        }
+        if let Some(target) = self.game.target {
+            game.target = utils::check_target(target).map_err(at("game.target"))?;
+        }
+        if let Some(undo_limit) = self.game.undo_limit {
+            game.undo_limit = undo_limit;
+        }
+
+        let spawn = &mut game.spawn;
+        if let Some(weights) = self.spawn.weights {
+            if weights.len() > SPAWN_VALUES.len() {
+                return Err(at("spawn.weights")(format!(
+                    "expected up to {} weights, of {:?}",
+                    SPAWN_VALUES.len(),
+                    SPAWN_VALUES
+                )));
+            }
+            spawn.weights = [0; 4];
+            spawn.weights[..weights.len()].copy_from_slice(&weights);
+        }
+        if let Some(per_move) = self.spawn.per_move {
+            spawn.per_move = per_move;
+        }
+        if let Some(initial) = self.spawn.initial {
+            spawn.initial = initial;
+        }
+        if let Some(scale) = self.spawn.scale {
+            spawn.scale = scale.parse().map_err(at("spawn.scale"))?;
+        }
+        if let Some(mode) = self.spawn.mode {
+            spawn.mode = mode.parse().map_err(at("spawn.mode"))?;
+        }
+        spawn.validate().map_err(at("spawn"))?;
+
+        if let Some(ms) = self.ui.tick_rate {
+            if !(MIN_TICK_RATE..=MAX_TICK_RATE).contains(&ms) {
+                return Err(at("ui.tick-rate")(format!(
+                    "{} is out of range, expected {} to {} ms",
+                    ms, MIN_TICK_RATE, MAX_TICK_RATE
+                )));
+            }
+            settings.tick_rate = Duration::from_millis(ms);
+        }
+        if let Some(animations) = self.ui.animations {
+            settings.options.no_animation = !animations;
+        }
+        if let Some(color) = self.ui.color {
+            settings.options.no_color = !color;
+        }
+        if let Some(ascii) = self.ui.ascii {
+            settings.options.ascii = ascii;
+        }
+        if let Some(depth) = self.ui.color_depth {
+            if depth != "auto" {
+                settings.color_depth = Some(depth.parse().map_err(at("ui.color-depth"))?);
+            }
+        }
+
+        self.theme.apply(&mut settings.theme, "theme.")?;
+        settings.keys = Keys::from_names(&self.keys)?;
+
+        Ok(settings)
+    }
+}
+
+/// name the key an error belongs to, e.g. `game.size`
+fn at(key: &str) -> impl Fn(String) -> String + '_ {
+    move |reason| format!("`{}`: {}", key, reason)
+}
+
+/// what a key does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Left,
+    Up,
+    Right,
+    Down,
+    Undo,
+    Redo,
+    Hint,
+    Autoplay,
+    Stats,
+    Restart,
+    KeepGoing,
+    Resume,
+    Discard,
+    Quit,
+}
+
+impl Action {
+    /// every action with its name in `config.toml`
+    pub const ALL: [(Action, &'static str); 14] = [
+        (Action::Left, "left"),
+        (Action::Up, "up"),
+        (Action::Right, "right"),
+        (Action::Down, "down"),
+        (Action::Undo, "undo"),
+        (Action::Redo, "redo"),
+        (Action::Hint, "hint"),
+        (Action::Autoplay, "autoplay"),
+        (Action::Stats, "stats"),
+        (Action::Restart, "restart"),
+        (Action::KeepGoing, "keep-going"),
+        (Action::Resume, "resume"),
+        (Action::Discard, "discard"),
+        (Action::Quit, "quit"),
+    ];
+
+    /// action by its name in `config.toml`, e.g. `keep-going`
+    pub fn named(name: &str) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(action, _)| *action)
+    }
+}
+
+/// key bindings, each action has one or more keys
+///
+/// Rules:
+///
+/// 1. actions missing from `config.toml` keep their default keys
+/// 2. a key does one thing, binding it to two actions is an error
+#[derive(Debug, Clone)]
+pub struct Keys {
+    bindings: Vec<(Action, Vec<Key>)>,
+    /// arrow labels are `<`, `^`, `>` and `v`
+    ascii: bool,
+}
+
+impl Default for Keys {
+    fn default() -> Keys {
+        let keys = |action| match action {
+            Action::Left => vec![Key::Left, Key::Char('h')],
+            Action::Up => vec![Key::Up, Key::Char('k')],
+            Action::Right => vec![Key::Right, Key::Char('l')],
+            Action::Down => vec![Key::Down, Key::Char('j')],
+            Action::Undo => vec![Key::Char('u')],
+            Action::Redo => vec![Key::Ctrl('r')],
+            Action::Hint => vec![Key::Char('?')],
+            Action::Autoplay => vec![Key::Char('a')],
+            Action::Stats => vec![Key::Char('s')],
+            Action::Restart => vec![Key::Char('r')],
+            Action::KeepGoing => vec![Key::Char('c')],
+            Action::Resume => vec![Key::Char('y')],
+            Action::Discard => vec![Key::Char('n')],
+            Action::Quit => vec![Key::Char('q')],
+        };
+
+        Keys {
+            bindings: Action::ALL
+                .iter()
+                .map(|(action, _)| (*action, keys(*action)))
+                .collect(),
+            ascii: false,
+        }
+    }
+}
+
+impl Keys {
+    /// `[keys]` section, e.g. `undo = ["u", "backspace"]`
+    fn from_names(names: &BTreeMap<String, Vec<String>>) -> Result<Keys, String> {
+        let mut keys = Keys::default();
+        for (name, key_names) in names {
+            let key = format!("keys.{}", name);
+            let action =
+                Action::named(name).ok_or_else(|| format!("`{}`: unknown action", key))?;
+            if key_names.is_empty() {
+                return Err(format!("`{}`: expected at least one key", key));
+            }
+            let parsed = key_names
+                .iter()
+                .map(|k| parse_key(k))
+                .collect::<Result<Vec<Key>, String>>()
+                .map_err(|e| format!("`{}`: {}", key, e))?;
+            if let Some(binding) = keys.bindings.iter_mut().find(|(a, _)| *a == action) {
+                binding.1 = parsed;
+            }
+        }
+
+        // a key does one thing
+        for (i, (action, bound)) in keys.bindings.iter().enumerate() {
+            for (other, other_bound) in &keys.bindings[i + 1..] {
+                if let Some(key) = bound.iter().find(|k| other_bound.contains(k)) {
+                    return Err(format!(
+                        "`{}` is bound to both `{}` and `{}`",
+                        key_name(*key),
+                        action_name(*action),
+                        action_name(*other)
+                    ));
+                }
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// what `key` does
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    /// first key of an action for the panel, e.g. `U` or `^R`
+    pub fn label(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .and_then(|(_, keys)| keys.first())
+            .map_or_else(String::new, |key| key_label(*key, self.ascii))
+    }
+
+    /// label keys with ASCII only
+    pub fn set_ascii(&mut self, ascii: bool) {
+        self.ascii = ascii;
+    }
+
+    /// keys of an action as written in `config.toml`, e.g. `left, h`
+    pub fn names(&self, action: Action) -> String {
+        let names: Vec<String> = self.keys(action).into_iter().map(key_name).collect();
+        names.join(", ")
+    }
+
+    /// keys of an action, e.g. quit keys which must never be dropped
+    pub fn keys(&self, action: Action) -> Vec<Key> {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or_else(Vec::new, |(_, keys)| keys.clone())
+    }
+}
+
+pub fn action_name(action: Action) -> &'static str {
+    Action::ALL
+        .iter()
+        .find(|(a, _)| *a == action)
+        .map_or("", |(_, name)| name)
+}
+
+/// named keys in `config.toml`
+const KEY_NAMES: [(&str, Key); 15] = [
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("space", Key::Char(' ')),
+    ("enter", Key::Char('\n')),
+    ("tab", Key::Char('\t')),
+    ("esc", Key::Esc),
+    ("backspace", Key::Backspace),
+    ("delete", Key::Delete),
+    ("insert", Key::Insert),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+];
+
+/// `u`, `?`, `left`, `space`, `ctrl-r`, `alt-x` or `f1`
+pub fn parse_key(name: &str) -> Result<Key, String> {
+    let invalid = || format!("unknown key `{}`", name);
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+
+    if let Some((_, key)) = KEY_NAMES.iter().find(|(n, _)| *n == name) {
+        return Ok(*key);
+    }
+    if let Some(c) = single(name) {
+        return Ok(Key::Char(c));
+    }
+    if let Some(c) = name.strip_prefix("ctrl-").and_then(single) {
+        return Ok(Key::Ctrl(c));
+    }
+    if let Some(c) = name.strip_prefix("alt-").and_then(single) {
+        return Ok(Key::Alt(c));
+    }
+    match name.strip_prefix('f').map(str::parse) {
+        Some(Ok(n)) if (1..=12).contains(&n) => Ok(Key::F(n)),
+        _ => Err(invalid()),
+    }
+}
+
+/// name of a key as written in `config.toml`
+fn key_name(key: Key) -> String {
+    match KEY_NAMES.iter().find(|(_, k)| *k == key) {
+        Some((name, _)) => name.to_string(),
+        None => match key {
+            Key::Char(c) => c.to_string(),
+            Key::Ctrl(c) => format!("ctrl-{}", c),
+            Key::Alt(c) => format!("alt-{}", c),
+            Key::F(n) => format!("f{}", n),
+            _ => format!("{:?}", key),
+        },
+    }
+}
+
+/// short name of a key for the panel
+fn key_label(key: Key, ascii: bool) -> String {
+    match key {
+        Key::Left | Key::Right | Key::Up | Key::Down => arrow(key, ascii).to_string(),
+        Key::Char(c) if !c.is_whitespace() => c.to_uppercase().to_string(),
+        Key::Ctrl(c) => format!("^{}", c.to_uppercase()),
+        Key::Alt(c) => format!("M-{}", c),
+        Key::Char(' ') => "Space".to_string(),
+        Key::Char('\n') => "Enter".to_string(),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::Esc => "Esc".to_string(),
+        Key::Backspace => "Bksp".to_string(),
+        Key::Delete => "Del".to_string(),
+        Key::Insert => "Ins".to_string(),
+        Key::PageUp => "PgUp".to_string(),
+        Key::PageDown => "PgDn".to_string(),
+        Key::F(n) => format!("F{}", n),
+        key => key_name(key),
+    }
+}
+
+/// arrow of an arrow key, e.g. `←` or `<`
+pub fn arrow(key: Key, ascii: bool) -> &'static str {
+    match (key, ascii) {
+        (Key::Left, false) => "←",
+        (Key::Up, false) => "↑",
+        (Key::Right, false) => "→",
+        (Key::Down, false) => "↓",
+        (Key::Left, true) => "<",
+        (Key::Up, true) => "^",
+        (Key::Right, true) => ">",
+        (Key::Down, true) => "v",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the error of a `config.toml`, `None` if it is valid
+    fn error(content: &str) -> Option<String> {
+        toml::from_str::<File>(content)
+            .map_err(|e| e.to_string())
+            .and_then(File::into_settings)
+            .err()
+    }
+
+    #[test]
+    fn valid_files_are_applied() {
+        let content = r#"
+            [game]
+            size = "3x5"
+            [ui]
+            tick-rate = 50
+            [keys]
+            undo = ["backspace", "z"]
+        "#;
+        let file: File = toml::from_str(content).unwrap();
+        let settings = file.into_settings().unwrap();
+        assert_eq!((settings.options.game.rows, settings.options.game.cols), (3, 5));
+        assert_eq!(settings.tick_rate, Duration::from_millis(50));
+        assert_eq!(settings.keys.action(Key::Char('z')), Some(Action::Undo));
+        assert_eq!(settings.keys.action(Key::Char('u')), None);
+    }
+
+    #[test]
+    fn errors_name_the_key() {
+        let unknown = error("[game]\nspeed = 3\n").unwrap();
+        assert!(unknown.contains("unknown field `speed`"), "{}", unknown);
+
+        let size = error("[game]\nsize = \"4by4\"\n").unwrap();
+        assert!(size.starts_with("`game.size`: invalid size `4by4`"), "{}", size);
+
+        for ms in [0, 5000] {
+            let tick_rate = error(&format!("[ui]\ntick-rate = {}\n", ms)).unwrap();
+            assert!(tick_rate.starts_with("`ui.tick-rate`: "), "{}", tick_rate);
+            assert!(tick_rate.contains("out of range"), "{}", tick_rate);
+        }
+
+        let action = error("[keys]\njump = [\"j\"]\n").unwrap();
+        assert_eq!(action, "`keys.jump`: unknown action");
+    }
+
+    #[test]
+    fn a_key_does_one_thing() {
+        let twice = error("[keys]\nundo = [\"q\"]\n").unwrap();
+        assert_eq!(twice, "`q` is bound to both `undo` and `quit`");
+    }
+}
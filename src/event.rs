@@ -1,7 +1,8 @@
+use std::collections::VecDeque;
 use std::io;
 use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use termion::event::Key;
 use termion::input::TermRead;
@@ -16,23 +17,37 @@ pub enum Event<I> {
 /// type is handled in its own thread and returned to a common `Receiver`
 #[allow(dead_code)]
 pub struct Events {
-    /// multi-producer-single-consumer
-    rx: mpsc::Receiver<Event<Key>>,
+    /// multi-producer-single-consumer, keys carry their arrival time
+    rx: mpsc::Receiver<(Event<Key>, Instant)>,
     /// keyboard events
     input_handle: thread::JoinHandle<()>,
     /// each tick
     tick_handle: thread::JoinHandle<()>,
+    /// keys read ahead of the game
+    buffer: InputBuffer,
+    /// a tick arrived while keys were waiting
+    tick_pending: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Config {
+    /// animations and timers, keys never wait for a tick
     pub tick_rate: Duration,
+    /// keys waiting to be handled
+    pub input_capacity: usize,
+    /// keys waiting longer than this are dropped
+    pub input_max_age: Duration,
+    /// keys which are never dropped, e.g. quit
+    pub keep: Vec<Key>,
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
             tick_rate: Duration::from_millis(250),
+            input_capacity: 16,
+            input_max_age: Duration::from_millis(500),
+            keep: vec![],
         }
     }
 }
@@ -44,7 +59,7 @@ impl Events {
     }
 
     /// config all your events for tui
-    /// 
+    ///
     /// keyboard events
     /// tick events
     pub fn with_config(config: Config) -> Events {
@@ -55,25 +70,24 @@ impl Events {
             let tx = tx.clone();
             thread::spawn(move || {
                 let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
-                        if let Err(err) = tx.send(Event::Input(key)) {
-                            eprintln!("{}", err);
-                            return;
-                        }
+                for key in stdin.keys().flatten() {
+                    // receiver is gone, the app is quitting
+                    if tx.send((Event::Input(key), Instant::now())).is_err() {
+                        return;
                     }
                 }
             })
         };
 
         // tick
+        let tick_rate = config.tick_rate;
         let tick_handle = {
             thread::spawn(move || loop {
-                if let Err(err) = tx.send(Event::Tick) {
-                    eprintln!("{}", err);
+                // receiver is gone, the app is quitting
+                if tx.send((Event::Tick, Instant::now())).is_err() {
                     break;
                 }
-                thread::sleep(config.tick_rate);
+                thread::sleep(tick_rate);
             })
         };
 
@@ -81,10 +95,136 @@ impl Events {
             rx,
             input_handle,
             tick_handle,
+            buffer: InputBuffer::new(config),
+            tick_pending: false,
         }
     }
 
-    pub fn next(&self) -> Result<Event<Key>, mpsc::RecvError> {
-        self.rx.recv()
+    /// next event, keys as soon as they arrive
+    ///
+    /// Rules:
+    ///
+    /// 1. everything already received goes into the input buffer first, so a burst is bounded
+    /// 2. buffered keys come before a pending tick, ticks are merged into one
+    /// 3. block only when nothing is waiting
+    pub fn next(&mut self) -> Result<Event<Key>, mpsc::RecvError> {
+        loop {
+            match self.rx.try_recv() {
+                Ok((Event::Input(key), at)) => self.buffer.push(key, at),
+                Ok((Event::Tick, _)) => self.tick_pending = true,
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return Err(mpsc::RecvError),
+            }
+        }
+
+        if let Some(key) = self.buffer.pop(Instant::now()) {
+            return Ok(Event::Input(key));
+        }
+        if self.tick_pending {
+            self.tick_pending = false;
+            return Ok(Event::Tick);
+        }
+
+        self.rx.recv().map(|(event, _)| event)
+    }
+}
+
+/// bounded buffer of keys waiting to be handled
+///
+/// Rules:
+///
+/// 1. keys keep their arrival order
+/// 2. when full, the oldest droppable key is dropped
+/// 3. keys older than `input_max_age` are dropped, they were meant for a board which is gone
+/// 4. keys matching `keep` are never dropped
+struct InputBuffer {
+    keys: VecDeque<(Key, Instant)>,
+    capacity: usize,
+    max_age: Duration,
+    keep: Vec<Key>,
+}
+
+impl InputBuffer {
+    fn new(config: Config) -> InputBuffer {
+        InputBuffer {
+            keys: VecDeque::new(),
+            capacity: config.input_capacity.max(1),
+            max_age: config.input_max_age,
+            keep: config.keep,
+        }
+    }
+
+    /// add a key, drop one if full
+    fn push(&mut self, key: Key, at: Instant) {
+        if self.keys.len() >= self.capacity {
+            match self.keys.iter().position(|(k, _)| !self.keep.contains(k)) {
+                Some(oldest) => {
+                    self.keys.remove(oldest);
+                }
+                // full of keys to keep, drop the new one unless it must be kept too
+                None if !self.keep.contains(&key) => return,
+                None => {}
+            }
+        }
+        self.keys.push_back((key, at));
+    }
+
+    /// oldest key which is not stale
+    fn pop(&mut self, now: Instant) -> Option<Key> {
+        while let Some((key, at)) = self.keys.pop_front() {
+            if self.keep.contains(&key) || now.duration_since(at) <= self.max_age {
+                return Some(key);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_capacity(capacity: usize, max_age: Duration) -> InputBuffer {
+        InputBuffer::new(Config {
+            input_capacity: capacity,
+            input_max_age: max_age,
+            keep: vec![Key::Char('q')],
+            ..Config::default()
+        })
+    }
+
+    fn drain(buffer: &mut InputBuffer, now: Instant) -> Vec<Key> {
+        std::iter::from_fn(|| buffer.pop(now)).collect()
+    }
+
+    #[test]
+    fn full_buffer_drops_the_oldest_droppable_key() {
+        let now = Instant::now();
+        let mut buffer = with_capacity(3, Duration::from_secs(1));
+        for key in ['q', 'h', 'j', 'k'] {
+            buffer.push(Key::Char(key), now);
+        }
+        assert_eq!(drain(&mut buffer, now), vec![Key::Char('q'), Key::Char('j'), Key::Char('k')]);
+
+        // full of keys to keep, only another one to keep gets in
+        let mut buffer = with_capacity(1, Duration::from_secs(1));
+        buffer.push(Key::Char('q'), now);
+        buffer.push(Key::Char('h'), now);
+        buffer.push(Key::Char('q'), now);
+        assert_eq!(drain(&mut buffer, now), vec![Key::Char('q'), Key::Char('q')]);
+    }
+
+    #[test]
+    fn stale_keys_are_dropped_unless_kept() {
+        let start = Instant::now();
+        let max_age = Duration::from_millis(500);
+        let mut buffer = with_capacity(8, max_age);
+        buffer.push(Key::Char('h'), start);
+        buffer.push(Key::Char('q'), start);
+        buffer.push(Key::Char('j'), start + max_age);
+
+        let now = start + max_age * 2;
+        assert_eq!(drain(&mut buffer, now), vec![Key::Char('q'), Key::Char('j')]);
     }
 }
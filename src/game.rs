@@ -1,5 +1,14 @@
-use rand::Rng;
+//! Rules, moves and game state.
 
+use std::{collections::VecDeque, fmt, str::FromStr, time::Duration};
+
+use rand::{Rng, RngCore, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+
+use crate::ai;
+use crate::bitboard::Board;
+use crate::replay::{Replay, Step};
 use crate::utils::equal_slice;
 
 /// Game
@@ -8,52 +17,629 @@ use crate::utils::equal_slice;
 /// 
 /// 1. a board to render your game grid
 /// 2. can calculate/get your game status
+#[derive(Serialize, Deserialize)]
 pub struct Game {
     /// alive or dead
-    pub alive: bool,
+    alive: bool,
+    /// target box is reached
+    won: bool,
+    /// keep going after winning
+    endless: bool,
+    /// merge points, each merge adds the value of the new box
+    score: i32,
+    /// how many moves changed the grid
+    moves: u32,
+    /// time spent on this game
+    played: Duration,
+    /// random seed, same seed and same commands make the same game
+    seed: u64,
+    /// game config
+    config: Config,
     /// board
     panel: Panel,
+    /// states before each move, the newest at the back
+    history: VecDeque<Snapshot>,
+    /// undone states, the newest at the back
+    future: Vec<Snapshot>,
+    /// how many times undo is used
+    undos: u32,
+    /// boxes inserted by `start`, for replays
+    #[serde(default)]
+    start: Vec<Spawn>,
+    /// every move, undo and redo, for replays
+    #[serde(default)]
+    steps: Vec<Step>,
+}
+
+/// game state which can be restored by undo/redo
+#[derive(Clone, Serialize, Deserialize)]
+struct Snapshot {
+    alive: bool,
+    won: bool,
+    score: i32,
+    moves: u32,
+    panel: Panel,
+}
+
+/// game config
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    /// grid rows
+    pub rows: usize,
+    /// grid columns
+    pub cols: usize,
+    /// merge rule
+    pub rule: Rule,
+    /// max undo steps, `0` means hardcore mode without undo
+    pub undo_limit: usize,
+    /// reach this box to win
+    pub target: i32,
+    /// how new boxes are inserted
+    #[serde(default)]
+    pub spawn: SpawnPolicy,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            rows: 4,
+            cols: 4,
+            rule: Rule::default(),
+            undo_limit: 10,
+            target: 2048,
+            spawn: SpawnPolicy::default(),
+        }
+    }
+}
+
+/// values a new box can take
+pub const SPAWN_VALUES: [i32; 4] = [2, 4, 8, 16];
+
+/// biggest box board-aware spawning inserts, so boxes can not grow without bound
+pub const MAX_SPAWN: i32 = 1024;
+
+/// biggest box a loaded game may hold, far above any real game,
+/// so merges of a broken save file can not overflow
+const MAX_LOADED_BOX: i32 = 1 << 24;
+
+/// how new boxes are inserted
+///
+/// Rules:
+///
+/// 1. a value is drawn from `SPAWN_VALUES` by `weights`, then scaled by `scale`
+/// 2. each box goes into a random empty cell
+/// 3. `mode` may pick the cell and the value instead, among values with a weight
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpawnPolicy {
+    /// weights of `SPAWN_VALUES`, `[9, 1, 0, 0]` inserts `2` 90% of the time and `4` 10%
+    pub weights: [u32; 4],
+    /// boxes inserted after each move
+    pub per_move: usize,
+    /// boxes inserted by `Game::start`
+    pub initial: usize,
+    /// how the board changes inserted values
+    pub scale: Scale,
+    /// who picks the new box
+    #[serde(default)]
+    pub mode: SpawnMode,
+}
+
+impl Default for SpawnPolicy {
+    fn default() -> SpawnPolicy {
+        SpawnPolicy {
+            weights: [9, 1, 0, 0],
+            per_move: 1,
+            initial: 2,
+            scale: Scale::Fixed,
+            mode: SpawnMode::Random,
+        }
+    }
+}
+
+impl SpawnPolicy {
+    /// possible values and their weights on a board whose biggest box is `max_box`
+    pub fn choices(&self, max_box: i32) -> impl Iterator<Item = (i32, u32)> + '_ {
+        let factor = self.scale.factor(max_box);
+        SPAWN_VALUES
+            .iter()
+            .zip(self.weights)
+            .filter(|(_, weight)| *weight > 0)
+            .map(move |(value, weight)| ((value * factor).min(MAX_SPAWN), weight))
+    }
+
+    /// draw a value on a board whose biggest box is `max_box`
+    fn pick<R: Rng>(&self, rng: &mut R, max_box: i32) -> i32 {
+        let total: u32 = self.weights.iter().sum();
+        let mut rand_num = rng.gen_range(0..total);
+        let mut val = SPAWN_VALUES[0];
+        for (num, weight) in self.choices(max_box) {
+            if rand_num < weight {
+                val = num;
+                break;
+            }
+            rand_num -= weight;
+        }
+
+        val
+    }
+
+    /// check the policy can make a game, so a broken config can not crash the game
+    pub fn validate(&self) -> Result<(), String> {
+        if self.weights.iter().all(|weight| *weight == 0) {
+            return Err("spawn weights are all zero".to_string());
+        }
+        if self.weights.iter().any(|weight| *weight > 1_000_000) {
+            return Err("spawn weight is bigger than 1000000".to_string());
+        }
+        if self.per_move == 0 {
+            return Err("at least one box must be inserted after each move".to_string());
+        }
+        if self.initial == 0 {
+            return Err("at least one box must be inserted at start".to_string());
+        }
+
+        self.scale.validate()
+    }
+}
+
+/// board-aware spawning
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Scale {
+    /// values are always taken from `SPAWN_VALUES`
+    #[default]
+    Fixed,
+    /// values are multiplied by `biggest box / n` once the biggest box reaches `n`,
+    /// up to `MAX_SPAWN`, e.g. with `1024` a board with `4096` inserts `8` instead of `2`
+    MaxBox(i32),
+}
+
+impl Scale {
+    /// multiplier of `SPAWN_VALUES` on a board whose biggest box is `max_box`
+    fn factor(&self, max_box: i32) -> i32 {
+        match self {
+            Scale::Fixed => 1,
+            Scale::MaxBox(n) => (max_box / n).max(1),
+        }
+    }
+
+    /// `n` must be a power of two and at least the biggest spawn value,
+    /// so a new box is never bigger than the biggest box
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Scale::MaxBox(n) if *n < SPAWN_VALUES[3] || n.count_ones() != 1 => Err(format!(
+                "invalid spawn scale `{}`, expected a power of two from {}",
+                n, SPAWN_VALUES[3]
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// who picks the new box
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SpawnMode {
+    /// random cell, value drawn by weights
+    #[default]
+    Random,
+    /// the box which leaves the worst best reply, like Evil 2048
+    Evil,
+    /// the box which leaves the best best reply, for practice
+    Kind,
+}
+
+impl fmt::Display for SpawnMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpawnMode::Random => write!(f, "random"),
+            SpawnMode::Evil => write!(f, "evil"),
+            SpawnMode::Kind => write!(f, "kind"),
+        }
+    }
+}
+
+impl FromStr for SpawnMode {
+    type Err = String;
+
+    /// `random`, `evil` or `kind`
+    fn from_str(s: &str) -> Result<SpawnMode, String> {
+        match s {
+            "random" => Ok(SpawnMode::Random),
+            "evil" => Ok(SpawnMode::Evil),
+            "kind" => Ok(SpawnMode::Kind),
+            _ => Err(format!(
+                "invalid spawn mode `{}`, expected `random`, `evil` or `kind`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Scale::Fixed => write!(f, "fixed"),
+            Scale::MaxBox(n) => write!(f, "max-box/{}", n),
+        }
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    /// `fixed` or `max-box/<n>`, e.g. `max-box/1024`
+    fn from_str(s: &str) -> Result<Scale, String> {
+        let invalid = || format!("invalid spawn scale `{}`, expected `fixed` or `max-box/<n>`", s);
+        let scale = match s.strip_prefix("max-box/") {
+            Some(n) => Scale::MaxBox(n.parse().map_err(|_| invalid())?),
+            None if s == "fixed" => Scale::Fixed,
+            None => return Err(invalid()),
+        };
+
+        scale.validate().map(|_| scale)
+    }
+}
+
+/// merge rule
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Rule {
+    /// classic 2048, each box can merge only once per move, `2 2 4 8 -> 4 4 8`
+    #[default]
+    Standard,
+    /// keep merging until nothing changes, `2 2 4 8 -> 16`
+    ChainMerge,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rule::Standard => write!(f, "standard"),
+            Rule::ChainMerge => write!(f, "chain"),
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    /// `standard` or `chain`
+    fn from_str(s: &str) -> Result<Rule, String> {
+        match s {
+            "standard" => Ok(Rule::Standard),
+            "chain" => Ok(Rule::ChainMerge),
+            _ => Err(format!("invalid rule `{}`, expected `standard` or `chain`", s)),
+        }
+    }
+}
+
+/// game status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// moves are accepted
+    Playing,
+    /// target box is reached, moves are ignored until `keep_going`
+    Won,
+    /// no legal move left
+    Over,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
 }
 
 impl Game {
+    /// create a default 4x4 game with a random seed
     pub fn new() -> Game {
+        Game::with_config(Config::default())
+    }
+
+    /// create a game with customized config and a random seed
+    pub fn with_config(config: Config) -> Game {
+        Game::from_rng(config, &mut rand::thread_rng())
+    }
+
+    /// create a game which seed is drawn from an injected random generator
+    ///
+    /// the game is still fully described by its seed, see `get_seed`
+    pub fn from_rng<R: RngCore>(config: Config, rng: &mut R) -> Game {
+        Game::from_seed(config, rng.next_u64())
+    }
+
+    /// create a reproducible game, same seed and same commands make the same game
+    pub fn from_seed(config: Config, seed: u64) -> Game {
         Game {
             alive: true,
-            panel: Panel::new(),
+            won: false,
+            endless: false,
+            score: 0,
+            moves: 0,
+            played: Duration::ZERO,
+            seed,
+            config,
+            panel: Panel::new(config.rows, config.cols, config.rule, config.spawn, seed),
+            history: VecDeque::new(),
+            future: vec![],
+            undos: 0,
+            start: vec![],
+            steps: vec![],
         }
     }
 
+    /// get game config
+    pub fn get_config(&self) -> Config {
+        self.config
+    }
+
+    /// get random seed
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// insert the first boxes, call it once before the first move
     pub fn start(&mut self) {
-        self.panel.init();
+        self.start = self.panel.init();
+    }
+
+    /// start with given boxes instead of random ones, e.g. from a replay
+    pub fn start_from(&mut self, spawns: &[Spawn]) -> Result<(), String> {
+        for spawn in spawns {
+            self.panel.place(*spawn)?;
+        }
+        self.start = spawns.to_vec();
+        self.alive = self.panel.check_alive();
+
+        Ok(())
+    }
 
-        self.panel.get_grid();
+    /// everything needed to play this game again
+    pub fn get_replay(&self) -> Replay {
+        Replay {
+            config: self.config,
+            seed: self.seed,
+            start: self.start.clone(),
+            steps: self.steps.clone(),
+        }
     }
 
+    /// get merge points
     pub fn get_score(&self) -> i32 {
+        self.score
+    }
+
+    /// get sum of all boxes
+    pub fn get_tile_sum(&self) -> i32 {
         self.panel
             .grid
             .iter()
-            .fold(0, |acc, x| acc + x.iter().fold(0, |row, y| row + y))
+            .map(|row| row.iter().sum::<i32>())
+            .sum()
+    }
+
+    /// get how many moves changed the grid
+    pub fn get_moves(&self) -> u32 {
+        self.moves
+    }
+
+    /// get time spent on this game
+    pub fn get_play_time(&self) -> Duration {
+        self.played
+    }
+
+    /// count time spent on this game
+    pub fn add_play_time(&mut self, time: Duration) {
+        self.played += time;
+    }
+
+    /// get the biggest box
+    pub fn get_max_box(&self) -> i32 {
+        self.panel.max_box()
+    }
+
+    /// get how many times undo is used
+    pub fn get_undos(&self) -> u32 {
+        self.undos
+    }
+
+    /// apply a command, a move which changes nothing is ignored, so is any move while won
+    pub fn next_tick(&mut self, cmd: Command) -> MoveOutcome {
+        // a random box always fits
+        self.play(cmd, None, true).unwrap_or_default()
+    }
+
+    /// same as `next_tick` without tracking each box, `movements` and `merges` are empty
+    ///
+    /// faster for headless play, e.g. simulations and the solver
+    pub fn next_tick_untracked(&mut self, cmd: Command) -> MoveOutcome {
+        self.play(cmd, None, false).unwrap_or_default()
+    }
+
+    /// apply a command and insert `spawns` instead of random boxes, e.g. from a replay
+    ///
+    /// the game is untouched if `spawns` do not fit
+    pub fn next_tick_with(
+        &mut self,
+        cmd: Command,
+        spawns: &[Spawn],
+    ) -> Result<MoveOutcome, String> {
+        self.play(cmd, Some(spawns), true)
     }
 
-    /// calculate next tick grid
-    pub fn next_tick(&mut self, cmd: Command) {
-        let grid_changed = self.panel.next_tick(cmd);
+    /// apply a command, insert `forced` or random boxes after it, `track` each box's movement
+    fn play(
+        &mut self,
+        cmd: Command,
+        forced: Option<&[Spawn]>,
+        track: bool,
+    ) -> Result<MoveOutcome, String> {
+        // waiting for `keep_going`
+        if self.is_won() {
+            return Ok(MoveOutcome::default());
+        }
+        let before = self.snapshot();
+        let (points, biggest, movements) = match self.panel.next_tick(cmd, track) {
+            Some(moved) => moved,
+            None => return Ok(MoveOutcome::default()),
+        };
+        // only a merge into a target box wins, not new boxes, neither this move's
+        // nor earlier ones, and not several smaller merges adding up to the target
+        let reached_target = !self.won && biggest >= self.config.target;
+
+        // a changed grid always has an empty box, the new boxes may leave no move
+        let spawns = match forced {
+            Some(spawns) => match spawns.iter().try_for_each(|spawn| self.panel.place(*spawn)) {
+                Ok(()) => spawns.to_vec(),
+                Err(reason) => {
+                    self.restore(before);
+                    return Err(reason);
+                }
+            },
+            None => self.panel.spawn_after_move(),
+        };
         self.alive = self.panel.check_alive();
+        self.score += points;
+        self.moves += 1;
+        self.won |= reached_target;
+        self.steps.push(Step::Move {
+            cmd,
+            spawns: spawns.clone(),
+        });
+
+        if self.config.undo_limit > 0 {
+            self.history.push_back(before);
+            if self.history.len() > self.config.undo_limit {
+                self.history.pop_front();
+            }
+            self.future.clear();
+        }
+
+        Ok(MoveOutcome {
+            changed: true,
+            points,
+            merges: merges(&movements),
+            movements,
+            spawns,
+            reached_target,
+            game_over: !self.alive,
+        })
+    }
 
-        if self.alive && grid_changed {
-            self.panel.random_insert();
+    /// get game status
+    pub fn get_status(&self) -> Status {
+        if !self.alive {
+            Status::Over
+        } else if self.is_won() {
+            Status::Won
+        } else {
+            Status::Playing
         }
     }
 
-    /// get grid
+    /// commands which change the grid, empty when the game is over or won
+    pub fn get_legal_moves(&self) -> Vec<Command> {
+        if self.is_won() {
+            return vec![];
+        }
+        Command::MOVES
+            .into_iter()
+            .filter(|cmd| slide(&self.panel.grid, *cmd, self.config.rule).is_some())
+            .collect()
+    }
+
+    /// some move is still possible
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// target box is reached and player has not chosen to keep going
+    pub fn is_won(&self) -> bool {
+        self.won && !self.endless
+    }
+
+    /// target box has been reached, even after keeping going
+    pub fn is_target_reached(&self) -> bool {
+        self.won
+    }
+
+    /// keep playing after winning
+    pub fn keep_going(&mut self) {
+        self.endless = true;
+    }
+
+    /// take back last move, returns false if nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(prev) => {
+                let curr = self.restore(prev);
+                self.future.push(curr);
+                self.undos += 1;
+                self.steps.push(Step::Undo);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// redo last undone move, returns false if nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.future.pop() {
+            Some(next) => {
+                let curr = self.restore(next);
+                self.history.push_back(curr);
+                self.steps.push(Step::Redo);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// check a loaded game, so a broken save file can not crash the game
+    pub fn validate(&self) -> Result<(), String> {
+        let config = self.config;
+        if config.rows == 0 || config.cols == 0 {
+            return Err(format!("invalid size {}x{}", config.rows, config.cols));
+        }
+        config.spawn.validate()?;
+
+        self.history
+            .iter()
+            .chain(self.future.iter())
+            .map(|state| &state.panel)
+            .chain(std::iter::once(&self.panel))
+            .try_for_each(|panel| panel.validate(&config))
+    }
+
+    /// current state
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            alive: self.alive,
+            won: self.won,
+            score: self.score,
+            moves: self.moves,
+            panel: self.panel.clone(),
+        }
+    }
+
+    /// restore a state, returns the replaced state
+    fn restore(&mut self, state: Snapshot) -> Snapshot {
+        let curr = self.snapshot();
+        self.alive = state.alive;
+        self.won = state.won;
+        self.score = state.score;
+        self.moves = state.moves;
+        self.panel = state.panel;
+
+        curr
+    }
+
+    /// get grid, `grid[row][col]`, `0` is an empty box
     pub fn get_grid(&self) -> Grid {
         self.panel.get_grid()
     }
 }
 
 /// game command
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Command {
     /// to left
     Left,
@@ -67,8 +653,54 @@ pub enum Command {
     Nil,
 }
 
-/// game grid
-pub type Grid = [[i32; 4]; 4];
+impl Command {
+    /// all commands which move boxes
+    pub const MOVES: [Command; 4] = [Command::Left, Command::Up, Command::Right, Command::Down];
+}
+
+/// what one move did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MoveOutcome {
+    /// the grid changed, otherwise the move is ignored and everything else is empty
+    pub changed: bool,
+    /// merge points earned
+    pub points: i32,
+    /// how each box moved, boxes which stay still included
+    pub movements: Vec<Movement>,
+    /// boxes made by merges as `((row, col), value)`
+    pub merges: Vec<((usize, usize), i32)>,
+    /// boxes inserted after the move
+    pub spawns: Vec<Spawn>,
+    /// this move reached the target box for the first time
+    pub reached_target: bool,
+    /// no move is left after this move
+    pub game_over: bool,
+}
+
+/// how one box moved in a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Movement {
+    /// `(row, col)` before the move
+    pub from: (usize, usize),
+    /// `(row, col)` after the move
+    pub to: (usize, usize),
+    /// value before the move
+    pub value: i32,
+    /// merged with other boxes at `to`
+    pub merged: bool,
+}
+
+/// a box inserted into an empty cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spawn {
+    /// `(row, col)`
+    pub cell: (usize, usize),
+    /// inserted number
+    pub value: i32,
+}
+
+/// game grid, `rows * cols`
+pub type Grid = Vec<Vec<i32>>;
 
 /// game grid panel
 /// 
@@ -76,52 +708,176 @@ pub type Grid = [[i32; 4]; 4];
 /// 
 /// 1. conain your grid
 /// 2. maybe other functions
+#[derive(Clone, Serialize, Deserialize)]
 struct Panel {
-    grid: [[i32; 4]; 4],
+    grid: Grid,
+    rows: usize,
+    cols: usize,
+    rule: Rule,
+    /// how new boxes are inserted
+    #[serde(default)]
+    spawn: SpawnPolicy,
+    /// seeded random generator for inserting numbers
+    rng: Pcg32,
 }
 
 impl Panel {
-    pub fn new() -> Panel {
-        Panel { grid: [[0; 4]; 4] }
+    pub fn new(rows: usize, cols: usize, rule: Rule, spawn: SpawnPolicy, seed: u64) -> Panel {
+        Panel {
+            grid: vec![vec![0; cols]; rows],
+            rows,
+            cols,
+            rule,
+            spawn,
+            rng: Pcg32::seed_from_u64(seed),
+        }
+    }
+
+    /// grid init, returns the new boxes
+    ///
+    /// start boxes are random in every spawn mode, so the seed still makes the game
+    pub fn init(&mut self) -> Vec<Spawn> {
+        (0..self.spawn.initial)
+            .map_while(|_| self.random_insert())
+            .collect()
+    }
+
+    /// insert boxes after a move, returns the new boxes
+    fn spawn_after_move(&mut self) -> Vec<Spawn> {
+        (0..self.spawn.per_move)
+            .map_while(|_| self.insert())
+            .collect()
+    }
+
+    /// insert one box the way the spawn mode says, returns the new box
+    fn insert(&mut self) -> Option<Spawn> {
+        match self.spawn.mode {
+            SpawnMode::Random => self.random_insert(),
+            SpawnMode::Evil => self.chosen_insert(true),
+            SpawnMode::Kind => self.chosen_insert(false),
+        }
     }
 
-    /// grid init
-    pub fn init(&mut self) {
-        self.random_insert();
-        self.random_insert();
+    /// insert a given box into an empty cell, only values a spawn can take
+    fn place(&mut self, spawn: Spawn) -> Result<(), String> {
+        let (i, j) = spawn.cell;
+        let valid = is_box(spawn.value) && spawn.value <= MAX_SPAWN;
+        match self.grid.get_mut(i).and_then(|row| row.get_mut(j)) {
+            Some(x) if *x == 0 && valid => {
+                *x = spawn.value;
+                Ok(())
+            }
+            Some(_) if valid => Err(format!("box ({}, {}) is not empty", i, j)),
+            Some(_) => Err(format!("invalid box {}", spawn.value)),
+            None => Err(format!("box ({}, {}) is out of the grid", i, j)),
+        }
     }
 
     /// get grid
     pub fn get_grid(&self) -> Grid {
-        self.grid
+        self.grid.clone()
     }
 
-    /// randomly insert one number into grid
-    /// 
-    /// ｜TODO maybe insert number should be determined by current numbers
-    pub fn random_insert(&mut self) {
-        let mut vec: Vec<(usize, usize)> = vec![];
-        for (i, row) in self.grid.iter().enumerate() {
-            for (j, x) in row.iter().enumerate() {
-                if *x == 0 {
-                    vec.push((i, j));
-                }
-            }
+    /// check grid matches the config
+    fn validate(&self, config: &Config) -> Result<(), String> {
+        if self.rows != config.rows
+            || self.cols != config.cols
+            || self.grid.len() != self.rows
+            || self.grid.iter().any(|row| row.len() != self.cols)
+        {
+            return Err(format!("grid is not {}x{}", config.rows, config.cols));
         }
 
+        let invalid = |x: &&i32| **x != 0 && !(is_box(**x) && **x <= MAX_LOADED_BOX);
+        match self.grid.iter().flatten().find(invalid) {
+            Some(x) => Err(format!("invalid box {}", x)),
+            None => Ok(()),
+        }
+    }
+
+    /// get the biggest box
+    pub fn max_box(&self) -> i32 {
+        self.grid
+            .iter()
+            .filter_map(|row| row.iter().max())
+            .max()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// randomly insert one number into grid, returns the new box
+    ///
+    /// the number is drawn by the spawn policy, which may look at the current numbers
+    pub fn random_insert(&mut self) -> Option<Spawn> {
+        let vec = self.empty_cells();
         let len = vec.len();
 
         if len == 0 {
-            return
+            return None
         }
 
-        let rand_num: usize = rand::thread_rng().gen_range(0..len);
+        let rand_num: usize = self.rng.gen_range(0..len);
         let (i, j) = vec[rand_num];
 
-        let rand_num = rand::thread_rng().gen_range(0..10);
-        let val = if rand_num < 6 { 2 } else { 4 };
+        let max_box = self.max_box();
+        let val = self.spawn.pick(&mut self.rng, max_box);
 
         self.grid[i][j] = val;
+
+        Some(Spawn {
+            cell: (i, j),
+            value: val,
+        })
+    }
+
+    /// insert the box which leaves the player the worst (`evil`) or the best best reply
+    ///
+    /// Rules:
+    ///
+    /// 1. try every empty cell with every value which has a weight
+    /// 2. score each try by the player's best move, rated by `ai::evaluate`, no move is the worst
+    /// 3. first try wins a tie, so same grid gets same box
+    fn chosen_insert(&mut self, evil: bool) -> Option<Spawn> {
+        let max_box = self.max_box();
+        let mut chosen: Option<(Spawn, f64)> = None;
+        for (i, j) in self.empty_cells() {
+            for (value, _) in self.spawn.choices(max_box) {
+                let mut grid = self.grid.clone();
+                grid[i][j] = value;
+                let reply = Command::MOVES
+                    .iter()
+                    .filter_map(|cmd| slide(&grid, *cmd, self.rule))
+                    .map(|(next, _, _)| ai::evaluate(&next))
+                    .fold(f64::MIN, f64::max);
+
+                let better = match chosen {
+                    Some((_, best)) if evil => reply < best,
+                    Some((_, best)) => reply > best,
+                    None => true,
+                };
+                if better {
+                    chosen = Some((Spawn { cell: (i, j), value }, reply));
+                }
+            }
+        }
+
+        let (spawn, _) = chosen?;
+        self.grid[spawn.cell.0][spawn.cell.1] = spawn.value;
+        Some(spawn)
+    }
+
+    /// `(row, col)` of every empty box
+    fn empty_cells(&self) -> Vec<(usize, usize)> {
+        let mut vec: Vec<(usize, usize)> = vec![];
+        for (i, row) in self.grid.iter().enumerate() {
+            for (j, x) in row.iter().enumerate() {
+                if *x == 0 {
+                    vec.push((i, j));
+                }
+            }
+        }
+
+        vec
     }
 
     /// check if alive
@@ -132,7 +888,7 @@ impl Panel {
     /// 2. if grid is filled, to check whether adjacent boxes are the same value, if it's, then return alive
     pub fn check_alive(&self) -> bool {
 
-        let has_zero = self.grid.iter().any(|row| row.iter().any(|x| *x == 0));
+        let has_zero = self.grid.iter().any(|row| row.contains(&0));
 
         if has_zero {
             return true;
@@ -156,12 +912,12 @@ impl Panel {
                 } else {
                     0
                 };
-                let right = if j != 3 {
+                let right = if j + 1 < self.cols {
                     self.grid[i][j + 1]
                 } else {
                     0
                 };
-                let down = if i != 3 {
+                let down = if i + 1 < self.rows {
                     self.grid[i + 1][j]
                 } else {
                     0
@@ -179,158 +935,265 @@ impl Panel {
         has_same
     }
 
-    /// calculate next grid
-    /// 
-    /// Rules
-    /// 
-    /// 1. calculate by Command
-    /// 2. recursion, merge each vector's same adjacent boxes
+    /// calculate next grid, movements are only worked out when `tracked`
     /// 
-    /// returns: true means value changes, false means no change
-    pub fn next_tick(&mut self, cmd: Command) -> bool {
-        let mut grid = self.grid.clone();
-
-        match cmd {
-            Command::Down => {
-                for y in 0..4 {
-                    let mut res = sum(vec![
-                        self.grid[0][y],
-                        self.grid[1][y],
-                        self.grid[2][y],
-                        self.grid[3][y],
-                    ]);
-
-                    loop {
-                        if res.len() < 4 {
-                            res.push(0);
-                        } else {
-                            break;
-                        }
-                    }
-
-                    res = res.into_iter().rev().collect();
-
-                    grid[0][y] = res[0];
-                    grid[1][y] = res[1];
-                    grid[2][y] = res[2];
-                    grid[3][y] = res[3];
-                }
-            }
-            Command::Up => {
-                for y in 0..4 {
-                    let mut res = sum(vec![
-                        self.grid[3][y],
-                        self.grid[2][y],
-                        self.grid[1][y],
-                        self.grid[0][y],
-                    ]);
-
-                    loop {
-                        if res.len() < 4 {
-                            res.push(0);
-                        } else {
-                            break;
-                        }
-                    }
-
-                    grid[0][y] = res[0];
-                    grid[1][y] = res[1];
-                    grid[2][y] = res[2];
-                    grid[3][y] = res[3];
-                }
-            }
-            Command::Left => {
-                for x in 0..4 {
-                    let mut res = sum(vec![
-                        self.grid[x][3],
-                        self.grid[x][2],
-                        self.grid[x][1],
-                        self.grid[x][0],
-                    ]);
-
-                    loop {
-                        if res.len() < 4 {
-                            res.push(0);
-                        } else {
-                            break;
-                        }
-                    }
-
-                    grid[x][0] = res[0];
-                    grid[x][1] = res[1];
-                    grid[x][2] = res[2];
-                    grid[x][3] = res[3];
-                }
-            }
-            Command::Right => {
-                for x in 0..4 {
-                    let mut res = sum(vec![
-                        self.grid[x][0],
-                        self.grid[x][1],
-                        self.grid[x][2],
-                        self.grid[x][3],
-                    ]);
-
-                    loop {
-                        if res.len() < 4 {
-                            res.push(0);
-                        } else {
-                            break;
-                        }
-                    }
-
-                    res = res.into_iter().rev().collect();
-
-                    grid[x][0] = res[0];
-                    grid[x][1] = res[1];
-                    grid[x][2] = res[2];
-                    grid[x][3] = res[3];
-                }
-            }
-            _ => {}
+    /// returns: `Some((points, biggest merged box, movements))` means value changes,
+    /// `None` means no change
+    pub fn next_tick(&mut self, cmd: Command, tracked: bool) -> Option<(i32, i32, Vec<Movement>)> {
+        let (grid, points, biggest) = slide(&self.grid, cmd, self.rule)?;
+        let movements = match tracked {
+            true => track(&self.grid, cmd, self.rule),
+            false => vec![],
+        };
+        self.grid = grid;
+
+        Some((points, biggest, movements))
+    }
+}
+
+/// calculate next grid without touching the game, e.g. for searching
+/// 
+/// standard 4x4 grids go through the bitboard engine, others through `slide_lines`
+/// 
+/// returns: `Some((grid, points, biggest merged box))` means value changes, `None` means no change,
+/// the biggest merged box is `0` when nothing merged
+pub fn slide(grid: &Grid, cmd: Command, rule: Rule) -> Option<(Grid, i32, i32)> {
+    if rule == Rule::Standard {
+        if let Some(board) = Board::from_grid(grid) {
+            return board
+                .slide(cmd)
+                .map(|(next, points, biggest)| (next.to_grid(), points as i32, biggest as i32));
         }
+    }
+
+    slide_lines(grid, cmd, rule)
+}
+
+/// calculate next grid line by line, works for any size and rule
+/// 
+/// Rules
+/// 
+/// 1. calculate by Command
+/// 2. merge each vector's same adjacent boxes by `Rule`
+pub(crate) fn slide_lines(grid: &Grid, cmd: Command, rule: Rule) -> Option<(Grid, i32, i32)> {
+    let mut next = grid.clone();
+    let mut points = 0;
+    let mut biggest = 0;
+    let cols = grid.first().map_or(0, |row| row.len());
 
-        // Fix:
-        // https://github.com/WanderHuang/game-2048-tui/issues/1
-        let is_changed = !is_equal_grid(&self.grid, &grid);
-        if is_changed {
-            self.grid = grid;
+    for line in lines(grid.len(), cols, cmd) {
+        let arr = line.iter().map(|&(i, j)| grid[i][j]).collect();
+        let (res, line_points, line_biggest) = match rule {
+            Rule::Standard => merge_once(arr),
+            Rule::ChainMerge => sum(arr),
+        };
+        points += line_points;
+        biggest = biggest.max(line_biggest);
+
+        // `res` starts from the edge which boxes move towards
+        for (k, &(i, j)) in line.iter().rev().enumerate() {
+            next[i][j] = res.get(k).copied().unwrap_or(0);
         }
+    }
 
-        is_changed
+    // Fix:
+    // https://github.com/WanderHuang/game-2048-tui/issues/1
+    if is_equal_grid(grid, &next) {
+        None
+    } else {
+        Some((next, points, biggest))
     }
 }
 
-/// recursive calculate vector's adjacent boxes
+/// how each box moves, merged the same way as `slide_lines`
+fn track(grid: &Grid, cmd: Command, rule: Rule) -> Vec<Movement> {
+    let cols = grid.first().map_or(0, |row| row.len());
+    let mut movements = vec![];
+
+    for line in lines(grid.len(), cols, cmd) {
+        let arr = line
+            .iter()
+            .enumerate()
+            .map(|(k, &(i, j))| Tracked {
+                value: grid[i][j],
+                first: k,
+                last: k,
+            })
+            .collect();
+        let (res, _, _) = match rule {
+            Rule::Standard => merge_once(arr),
+            Rule::ChainMerge => sum(arr),
+        };
+
+        for (tracked, &to) in res.iter().zip(line.iter().rev()) {
+            let merged = tracked.first != tracked.last;
+            movements.extend(
+                line[tracked.first..=tracked.last]
+                    .iter()
+                    .filter(|&&(i, j)| grid[i][j] != 0)
+                    .map(|&from| Movement {
+                        from,
+                        to,
+                        value: grid[from.0][from.1],
+                        merged,
+                    }),
+            );
+        }
+    }
+
+    movements
+}
+
+/// boxes made by merges, the value is the sum of boxes moved into the cell
+fn merges(movements: &[Movement]) -> Vec<((usize, usize), i32)> {
+    let mut merges: Vec<((usize, usize), i32)> = vec![];
+    for m in movements.iter().filter(|m| m.merged) {
+        match merges.iter_mut().find(|(cell, _)| *cell == m.to) {
+            Some((_, value)) => *value += m.value,
+            None => merges.push((m.to, m.value)),
+        }
+    }
+
+    merges
+}
+
+/// a box which can merge with another box of the same value
+trait Tile {
+    fn value(&self) -> i32;
+    /// merge `other` into this box
+    fn absorb(&mut self, other: Self);
+}
+
+impl Tile for i32 {
+    fn value(&self) -> i32 {
+        *self
+    }
+
+    fn absorb(&mut self, other: i32) {
+        *self += other;
+    }
+}
+
+/// a box which remembers where it came from
 ///
-/// 1 2 2 4 -> 1 8
-fn sum(arr: Vec<i32>) -> Vec<i32> {
+/// merged boxes are always adjacent once empty boxes are skipped,
+/// so the sources are the non-empty boxes between `first` and `last` of the line
+struct Tracked {
+    value: i32,
+    first: usize,
+    last: usize,
+}
+
+impl Tile for Tracked {
+    fn value(&self) -> i32 {
+        self.value
+    }
+
+    fn absorb(&mut self, other: Tracked) {
+        self.value += other.value;
+        self.first = self.first.min(other.first);
+        self.last = self.last.max(other.last);
+    }
+}
+
+/// split grid into lines by Command
+/// 
+/// each line is ordered from the far side to the edge which boxes move towards
+fn lines(rows: usize, cols: usize, cmd: Command) -> Vec<Vec<(usize, usize)>> {
+    match cmd {
+        Command::Down => (0..cols)
+            .map(|j| (0..rows).map(|i| (i, j)).collect())
+            .collect(),
+        Command::Up => (0..cols)
+            .map(|j| (0..rows).rev().map(|i| (i, j)).collect())
+            .collect(),
+        Command::Left => (0..rows)
+            .map(|i| (0..cols).rev().map(|j| (i, j)).collect())
+            .collect(),
+        Command::Right => (0..rows)
+            .map(|i| (0..cols).map(|j| (i, j)).collect())
+            .collect(),
+        Command::Nil => vec![],
+    }
+}
+
+/// merge vector's adjacent boxes, each box merges at most once
+///
+/// 2 2 2 2 -> 4 4, 8 points, biggest merge 4
+/// 1 2 2 4 -> 1 4 4, 4 points, biggest merge 4
+///
+/// returns: `(boxes, points, biggest merged box)`, the biggest is `0` when nothing merged
+fn merge_once<T: Tile>(arr: Vec<T>) -> (Vec<T>, i32, i32) {
+    let mut res: Vec<T> = Vec::new();
+    let mut points = 0;
+    let mut biggest = 0;
+    // the last box of `res` is a merged one
+    let mut merged = false;
+
+    for curr in arr.into_iter().rev().filter(|x| x.value() != 0) {
+        match res.last_mut() {
+            Some(x) if x.value() == curr.value() && !merged => {
+                x.absorb(curr);
+                points += x.value();
+                biggest = biggest.max(x.value());
+                merged = true;
+            }
+            _ => {
+                res.push(curr);
+                merged = false;
+            }
+        }
+    }
+
+    (res, points, biggest)
+}
+
+/// recursive calculate vector's adjacent boxes (chain merge)
+///
+/// 1 2 2 4 -> 1 8, 12 points, biggest merge 8
+///
+/// returns: same as `merge_once`
+fn sum<T: Tile>(arr: Vec<T>) -> (Vec<T>, i32, i32) {
     let mut added = false;
-    let res = arr.into_iter().rev().fold(Vec::new(), |mut acc, curr| {
+    let mut points = 0;
+    let mut biggest = 0;
+    let res = arr.into_iter().rev().fold(Vec::new(), |mut acc: Vec<T>, curr| {
         if let Some(x) = acc.last_mut() {
-            if x == &curr {
-                *x = curr * 2;
+            if x.value() == curr.value() {
+                x.absorb(curr);
+                points += x.value();
+                biggest = biggest.max(x.value());
                 added = true;
-            } else if curr != 0 {
+            } else if curr.value() != 0 {
                 acc.push(curr);
             }
-        } else if curr != 0 {
+        } else if curr.value() != 0 {
             acc.push(curr);
         }
 
-        return acc;
+        acc
     });
 
     if added {
-        return sum(res.into_iter().rev().collect());
+        let (res, more, next_biggest) = sum(res.into_iter().rev().collect());
+        return (res, points + more, biggest.max(next_biggest));
     }
 
-    res
+    (res, points, biggest)
 }
 
 
+/// a power of two from 2, the values a box can take
+fn is_box(x: i32) -> bool {
+    x >= 2 && x.count_ones() == 1
+}
+
 /// check if two grid is equal
-fn is_equal_grid<T>(a: &[[T; 4]; 4], b: &[[T; 4]; 4]) -> bool where T: Eq {
+fn is_equal_grid<T>(a: &[Vec<T>], b: &[Vec<T>]) -> bool where T: Eq {
+    if a.len() != b.len() {
+        return false
+    }
+
     for (i, a_row) in a.iter().enumerate() {
         let eq = equal_slice(a_row, &b[i]);
         if !eq {
@@ -340,3 +1203,176 @@ fn is_equal_grid<T>(a: &[[T; 4]; 4], b: &[[T; 4]; 4]) -> bool where T: Eq {
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(target: i32, start: &[Spawn]) -> Game {
+        let config = Config {
+            target,
+            ..Config::default()
+        };
+        let mut game = Game::from_seed(config, 1);
+        game.start_from(start).unwrap();
+        game
+    }
+
+    fn spawn(cell: (usize, usize), value: i32) -> Spawn {
+        Spawn { cell, value }
+    }
+
+    /// slide one row, on the line engine and, for 4 boxes, the bitboard engine too
+    fn slide_row(row: &[i32], cmd: Command, rule: Rule) -> (Vec<i32>, i32) {
+        let grid = vec![row.to_vec()];
+        let (next, points, biggest) = slide(&grid, cmd, rule).unwrap();
+        if row.len() == 4 {
+            let mut square = vec![vec![0; 4]; 4];
+            square[0] = row.to_vec();
+            let (square, square_points, square_biggest) = slide(&square, cmd, rule).unwrap();
+            assert_eq!((&square[0], square_points, square_biggest), (&next[0], points, biggest));
+        }
+        (next[0].clone(), points)
+    }
+
+    #[test]
+    fn standard_rule_merges_each_box_once() {
+        let rule = Rule::Standard;
+        assert_eq!(slide_row(&[2, 2, 4, 8], Command::Left, rule), (vec![4, 4, 8, 0], 4));
+        assert_eq!(slide_row(&[2, 2, 2, 2], Command::Left, rule), (vec![4, 4, 0, 0], 8));
+        assert_eq!(slide_row(&[2, 2, 2], Command::Right, rule), (vec![0, 2, 4], 4));
+        assert_eq!(slide_row(&[2, 2, 2, 0], Command::Right, rule), (vec![0, 0, 2, 4], 4));
+    }
+
+    #[test]
+    fn chain_rule_merges_until_nothing_changes() {
+        let rule = Rule::ChainMerge;
+        // 2 + 2, then 4 + 4, then 8 + 8
+        assert_eq!(slide_row(&[2, 2, 4, 8], Command::Left, rule), (vec![16, 0, 0, 0], 28));
+        assert_eq!(slide_row(&[2, 2, 2, 2], Command::Left, rule), (vec![8, 0, 0, 0], 16));
+    }
+
+    #[test]
+    fn only_merges_reach_the_target() {
+        // two merges into 4s add up to 8 points, but no merge made an 8
+        let start = [
+            spawn((0, 3), 8),
+            spawn((1, 0), 2),
+            spawn((1, 1), 2),
+            spawn((2, 0), 2),
+            spawn((2, 1), 2),
+        ];
+        let mut sums = game(8, &start);
+        let outcome = sums.next_tick_with(Command::Left, &[spawn((3, 3), 2)]).unwrap();
+        assert_eq!(outcome.merges, vec![((1, 0), 4), ((2, 0), 4)]);
+        assert_eq!(outcome.points, 8);
+        assert!(!outcome.reached_target);
+        assert!(!sums.is_won());
+
+        let mut game = game(4, &[spawn((0, 0), 2)]);
+        let outcome = game.next_tick_with(Command::Right, &[spawn((1, 1), 4)]).unwrap();
+        assert!(!outcome.reached_target);
+        assert!(!game.is_won());
+
+        let outcome = game.next_tick_with(Command::Right, &[spawn((0, 2), 2)]).unwrap();
+        assert!(!outcome.reached_target);
+        let outcome = game.next_tick_with(Command::Right, &[spawn((2, 2), 2)]).unwrap();
+        assert!(outcome.reached_target);
+        assert!(game.is_won());
+    }
+
+    #[test]
+    fn oversized_boxes_are_refused() {
+        let mut fresh = Game::from_seed(Config::default(), 1);
+        assert!(fresh.start_from(&[spawn((0, 0), MAX_SPAWN * 2)]).is_err());
+
+        let game = game(2048, &[spawn((0, 0), 2), spawn((0, 1), 2)]);
+        assert!(game.validate().is_ok());
+        let json = serde_json::to_string(&game).unwrap();
+        let json = json.replacen("[[2,2,", "[[1073741824,1073741824,", 1);
+        let broken: Game = serde_json::from_str(&json).unwrap();
+        assert!(broken.validate().is_err());
+    }
+
+    #[test]
+    fn evil_and_kind_spawns_are_chosen() {
+        let grid = vec![
+            vec![2, 4, 8, 16],
+            vec![0, 0, 4, 8],
+            vec![0, 0, 0, 2],
+            vec![0, 0, 0, 0],
+        ];
+        let chosen = |mode: SpawnMode, seed: u64| {
+            let spawn = SpawnPolicy {
+                weights: [1, 1, 0, 0],
+                mode,
+                ..SpawnPolicy::default()
+            };
+            let mut panel = Panel::new(4, 4, Rule::Standard, spawn, seed);
+            panel.grid = grid.clone();
+            let chosen = panel.insert().unwrap();
+            assert_eq!(panel.grid[chosen.cell.0][chosen.cell.1], chosen.value);
+            chosen
+        };
+
+        let evil = chosen(SpawnMode::Evil, 1);
+        let kind = chosen(SpawnMode::Kind, 1);
+        // evil blocks the corner under the `2`, kind lines a `4` up with the other `4`
+        assert_eq!(evil, spawn((3, 3), 4));
+        assert_eq!(kind, spawn((1, 0), 4));
+        assert_ne!(evil, kind);
+        // the seed plays no part, same grid always gets the same box
+        for seed in 2..10 {
+            assert_eq!(chosen(SpawnMode::Evil, seed), evil);
+            assert_eq!(chosen(SpawnMode::Kind, seed), kind);
+        }
+    }
+
+    #[test]
+    fn undo_restores_the_spawner() {
+        let mut game = Game::from_seed(Config::default(), 3);
+        game.start();
+        let cmd = game.get_legal_moves()[0];
+        let first = game.next_tick(cmd);
+        let grid = game.get_grid();
+
+        // same move after an undo gets the same new box
+        assert!(game.undo());
+        assert!(!game.undo());
+        let again = game.next_tick(cmd);
+        assert_eq!(again.spawns, first.spawns);
+        assert_eq!(game.get_grid(), grid);
+
+        // a new move forgets what was undone
+        assert!(game.undo());
+        game.next_tick(game.get_legal_moves()[0]);
+        assert!(!game.redo());
+
+        let config = Config {
+            undo_limit: 0,
+            ..Config::default()
+        };
+        let mut game = Game::from_seed(config, 3);
+        game.start();
+        game.next_tick(cmd);
+        assert!(!game.undo());
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn won_games_wait_for_keep_going() {
+        let mut game = game(4, &[spawn((0, 0), 2), spawn((0, 1), 2)]);
+        game.next_tick_with(Command::Left, &[spawn((1, 1), 2)]).unwrap();
+        assert_eq!(game.get_status(), Status::Won);
+        assert!(game.get_legal_moves().is_empty());
+        let grid = game.get_grid();
+        assert!(!game.next_tick(Command::Right).changed);
+        assert!(!game.next_tick_with(Command::Right, &[]).unwrap().changed);
+        assert_eq!(game.get_grid(), grid);
+
+        game.keep_going();
+        assert_eq!(game.get_status(), Status::Playing);
+        assert!(!game.get_legal_moves().is_empty());
+        assert!(game.next_tick(Command::Right).changed);
+    }
+}
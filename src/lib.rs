@@ -0,0 +1,51 @@
+//! 2048 game engine, AI strategies and batch simulation shared by the binaries.
+//!
+//! The TUI is one consumer of this crate, bots and tools can drive the same engine:
+//!
+//! ```
+//! use tui_2048::{Config, Game, Rule, Status};
+//!
+//! let config = Config {
+//!     rows: 4,
+//!     cols: 4,
+//!     rule: Rule::Standard,
+//!     ..Config::default()
+//! };
+//! // same seed and same commands make the same game
+//! let mut game = Game::from_seed(config, 42);
+//! game.start();
+//!
+//! while game.get_status() == Status::Playing {
+//!     let moves = game.get_legal_moves();
+//!     // what the move did: points, merges, movements, the new boxes, game over
+//!     let outcome = game.next_tick(moves[0]);
+//!     assert!(outcome.changed && !outcome.spawns.is_empty());
+//! }
+//!
+//! let grid = game.get_grid();
+//! assert_eq!(grid.len(), 4);
+//! assert!(game.get_score() > 0);
+//! assert!(matches!(game.get_status(), Status::Won | Status::Over));
+//! ```
+//!
+//! Modules:
+//!
+//! 1. `game`, rules, moves and game state
+//! 2. `bitboard`, packed 4x4 board used by `game` and `ai` for speed
+//! 3. `ai`, strategies which pick the next command
+//! 4. `replay`, recorded games as JSON or compact text
+//! 5. `sim`, play many games headless and summarize them
+//! 6. `utils`, small helpers for formatting and parsing
+
+#![warn(missing_docs)]
+
+pub mod ai;
+pub mod bitboard;
+pub mod game;
+pub mod replay;
+pub mod sim;
+pub mod utils;
+
+pub use game::{
+    Command, Config, Game, Grid, MoveOutcome, Movement, Rule, Spawn, SpawnPolicy, Status,
+};
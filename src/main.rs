@@ -1,26 +1,102 @@
 mod app;
+mod board;
+mod cli;
+mod config;
 mod event;
-mod game;
-mod utils;
+mod records;
+mod save;
+mod text;
+mod theme;
+mod ui;
 
-use std::{error::Error, io, time::Duration};
+use std::{env, error::Error, fs, io, path::PathBuf, process};
 use termion::{event::Key, input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
-use tui::{
-    backend::TermionBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Color},
-    widgets::{
-        canvas::{Canvas, Line, Points},
-        Block, Borders,
-    },
-    Terminal,
-};
+use tui::{backend::TermionBackend, Terminal};
+
+use tui_2048::{ai, game, replay, sim, utils};
 
 use app::App;
+use cli::{Options, Run};
+use config::{Action, Settings};
 use event::{Config, Event, Events};
-use game::Command;
+use game::{Command, Game};
+use replay::Replay;
+use theme::ColorDepth;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // a broken config is reported before the screen is taken
+    let mut settings = match config::load() {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    // flags override the config file
+    let (run, mut options) = match cli::parse(env::args().skip(1), settings.options) {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+    if let Some(theme) = options.theme.take() {
+        settings.theme = theme;
+    }
+    let depth = settings.color_depth.unwrap_or_else(ColorDepth::detect);
+    settings.theme = settings.theme.with_depth(depth);
+    if options.no_color {
+        settings.theme = settings.theme.without_color();
+    }
+    settings.keys.set_ascii(options.ascii);
+
+    match run {
+        Run::Play => {
+            settings.options = options;
+            play(settings, None)
+        }
+        Run::Replay(path) => {
+            // a broken replay is reported before the screen is taken
+            let replay = match save::load_replay(&path) {
+                Ok(replay) => replay,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    process::exit(2);
+                }
+            };
+            settings.options = options;
+            play(settings, Some(replay))
+        }
+        Run::Text => {
+            settings.options = options;
+            let stdout = io::stdout();
+            text::Text::new(&settings, stdout.lock()).run(io::stdin().lock())
+        }
+        Run::Solve { keep_going, out } => solve(&options, keep_going, out),
+        Run::Stats { json } => print_stats(json),
+        Run::Simulate(config, json) => {
+            let report = sim::run(&config);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{}", report);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// play or watch a replay in the terminal
+fn play(settings: Settings, replay: Option<Replay>) -> Result<(), Box<dyn Error>> {
+    let Settings {
+        options,
+        tick_rate,
+        theme,
+        keys,
+        ..
+    } = settings;
+
     let stdout = io::stdout().into_raw_mode()?;
     let stdout = MouseTerminal::from(stdout);
     let stdout = AlternateScreen::from(stdout);
@@ -28,199 +104,206 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut terminal = Terminal::new(backend)?;
 
     let config = Config {
-        tick_rate: Duration::from_millis(250),
+        // fast enough for animations, moves never wait for it
+        tick_rate,
+        // quitting must never be lost
+        keep: keys.keys(Action::Quit),
         ..Default::default()
     };
-    let events = Events::with_config(config);
+    let mut events = Events::with_config(config);
 
-    let mut app = App::new();
+    let mut app = App::new(options.game, options.seed);
+    app.set_ai_config(options.ai);
+    app.set_animations(!options.no_animation);
+    if let Some(replay) = replay {
+        app.watch(replay)?;
+    } else if options.seed.is_none() {
+        // a given seed means a new game, nothing to resume
+        match save::load() {
+            Ok(Some(saved)) => app.offer_resume(saved),
+            Ok(None) => {}
+            Err(err) => app.set_notice(format!("save file ignored, {}", err)),
+        }
+    }
+    match records::load() {
+        Ok(records) => app.set_records(records),
+        Err(err) => app.set_notice(format!("records ignored, {}", err)),
+    }
 
+    // draw only when something changed
+    let mut dirty = true;
+    // the board reflows when the terminal is resized
+    let mut size = terminal.size()?;
     loop {
-        terminal.draw(|f| {
-            let chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
-                .split(f.size());
-            // params
-            let board_size = app.get_size();
-            let panel_size = board_size + (board_size / 3.0);
-            let half_box_size = app.box_size / 2.0;
-            let font_width = 2.0;
-            // Game board
-            let canvas = Canvas::default()
-                .block(Block::default().borders(Borders::ALL).title("2048-@wander"))
-                .paint(|ctx| {
-                    let grid = app.get_grid();
-                    for (row, list) in grid.iter().enumerate() {
-                        for (col, _) in list.iter().enumerate() {
-                            // 盒子参数
-                            let score = grid[row][col];
-                            let s = if score == 0 {
-                                String::from("").into_boxed_str()
-                            } else {
-                                pad_str(score.to_owned().to_string(), 6).into_boxed_str()
-                            };
-                            let x_box = (col as f64) * app.box_size;
-                            let y_box = (row as f64) * app.box_size;
-                            ctx.print(
-                                ((col + 1) as f64) * app.box_size - half_box_size - font_width,
-                                ((4 - row) as f64) * app.box_size
-                                    - half_box_size
-                                    - font_width * 2.0,
-                                Box::leak(s),
-                                score_to_color(score),
-                            );
-                            ctx.draw(&Line {
-                                x1: x_box,
-                                y1: y_box,
-                                x2: x_box + app.box_size,
-                                y2: y_box,
-                                color: Color::Green,
-                            });
-                            ctx.draw(&Line {
-                                x1: x_box,
-                                y1: y_box,
-                                x2: x_box,
-                                y2: y_box + app.box_size,
-                                color: Color::Green,
-                            });
-                            ctx.draw(&Line {
-                                x1: x_box + app.box_size,
-                                y1: y_box,
-                                x2: x_box + app.box_size,
-                                y2: y_box + app.box_size,
-                                color: Color::Green,
-                            });
-                            ctx.draw(&Line {
-                                x1: x_box,
-                                y1: y_box + app.box_size,
-                                x2: x_box + app.box_size,
-                                y2: y_box + app.box_size,
-                                color: Color::Green,
-                            });
-                        }
-                    }
-
-                    if !app.is_alive() {
-
-                        ctx.draw(&Points {
-                            coords: &app.get_game_over_modal(),
-                            color: Color::Green
-                        });
-
-                        ctx.print(
-                            app.box_size * 1.5,
-                            app.box_size * 2.0,
-                            " GAME OVER! ",
-                            Color::Blue,
-                        );
-
-                        ctx.print(
-                            app.box_size * 1.3,
-                            app.box_size * 1.8,
-                            " Restart[R] Quit[Q] ",
-                            Color::Blue,
-                        );
-                    }
-                })
-                .x_bounds([0.0, board_size])
-                .y_bounds([0.0, board_size]);
-            f.render_widget(canvas, chunks[0]);
-            // Informantions
-            let canvas = Canvas::default()
-                .block(Block::default().borders(Borders::ALL).title("Panel"))
-                .paint(|ctx| {
-                    ctx.print(board_size, board_size, "> Relax <", Color::Blue);
-
-                    let score = app.get_score().to_owned().to_string().into_boxed_str();
-                    ctx.print(board_size, board_size - 30.0, "Score:", Color::Green);
-                    ctx.print(
-                        board_size,
-                        board_size - 40.0,
-                        Box::leak(score),
-                        Color::Green,
-                    );
-
-                    ctx.print(board_size, 0.0, "Quit[Q]", Color::Blue);
-                })
-                .x_bounds([board_size, panel_size])
-                .y_bounds([0.0, board_size]);
-            f.render_widget(canvas, chunks[1]);
-        })?;
+        if dirty {
+            terminal.draw(|f| ui::draw(f, &app, &theme, &keys, options.ascii))?;
+        }
 
         // Events
         match events.next()? {
-            Event::Input(input) => match input {
-                Key::Char('q') => {
-                    break;
-                }
-                Key::Char('r') => {
-                    app.restart();
-                }
-                // left up right down
-                Key::Down => {
-                    app.add_command(Command::Down);
-                }
-                Key::Up => {
-                    app.add_command(Command::Up);
-                }
-                Key::Right => {
-                    app.add_command(Command::Right);
-                }
-                Key::Left => {
-                    app.add_command(Command::Left);
-                }
-                // h k l j   vim keys support
-                Key::Char('h') => {
-                    app.add_command(Command::Left);
-                }
-                Key::Char('k') => {
-                    app.add_command(Command::Up);
-                }
-                Key::Char('l') => {
-                    app.add_command(Command::Right);
-                }
-                Key::Char('j') => {
-                    app.add_command(Command::Down);
+            Event::Input(input) => {
+                dirty = true;
+                app.clear_notice();
+                if app.is_replay() {
+                    match (input, keys.action(input)) {
+                        (_, Some(Action::Quit)) => break,
+                        (Key::Char(' '), _) => app.toggle_pause(),
+                        (_, Some(Action::Right)) => app.step_forward(),
+                        (_, Some(Action::Left)) => app.step_back(),
+                        (Key::Char('+'), _) | (Key::Char('='), _) => app.faster(),
+                        (Key::Char('-'), _) => app.slower(),
+                        _ => {}
+                    }
+                    continue;
                 }
-                _ => {
-                    app.add_command(Command::Nil);
+                match keys.action(input) {
+                    Some(Action::Quit) => {
+                        break;
+                    }
+                    Some(Action::Resume) => {
+                        app.resume();
+                    }
+                    Some(Action::Discard) => {
+                        app.discard_resume();
+                    }
+                    Some(Action::Restart) => {
+                        app.restart();
+                    }
+                    Some(Action::KeepGoing) => {
+                        app.keep_going();
+                    }
+                    Some(Action::Stats) => {
+                        app.toggle_stats();
+                    }
+                    Some(Action::Hint) => {
+                        app.hint();
+                    }
+                    Some(Action::Autoplay) => {
+                        app.toggle_autoplay();
+                    }
+                    Some(Action::Undo) => {
+                        app.undo();
+                    }
+                    Some(Action::Redo) => {
+                        app.redo();
+                    }
+                    action => {
+                        app.add_command(action_to_command(action));
+                    }
                 }
-            },
+            }
             Event::Tick => {
-                app.next()
+                let resized = terminal.size()? != size;
+                size = terminal.size()?;
+                dirty = app.next() || resized;
             }
         }
     }
 
+    let saved = app.save();
+    // leave alternate screen before printing errors
+    drop(terminal);
+    if let Err(err) = saved {
+        eprintln!("failed to save game, {}", err);
+    }
+
     Ok(())
 }
 
-/// make different strings as same length
-fn pad_str(s: String, length: usize) -> String {
-    let mut s = s.clone();
-    loop {
-        if s.len() < length {
-            s.push_str(" ");
-        } else {
-            break;
+/// `stats`: high-score table and lifetime statistics on stdout
+fn print_stats(json: bool) -> Result<(), Box<dyn Error>> {
+    let records = records::load()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+
+    let best = records.high_scores.first().map_or(0, |r| r.score);
+    println!(
+        "Games played: {}    Won: {} ({:.1}%)    Best score: {}",
+        records.games_played,
+        records.games_won,
+        records.win_rate(),
+        best
+    );
+    if records.high_scores.is_empty() {
+        return Ok(());
+    }
+
+    let print_row = |row: &[String]| {
+        println!(
+            "{:>3} {:>9} {:>7} {:>7} {:>9} {:>7} {:>9} {:>10}",
+            row[0], row[1], row[2], row[3], row[4], row[5], row[6], row[7]
+        )
+    };
+    println!();
+    print_row(&ui::RECORD_HEADER.map(String::from));
+    for (i, r) in records.high_scores.iter().enumerate() {
+        print_row(&ui::record_row(i, r));
+    }
+    println!("* used undo");
+
+    println!();
+    println!("Highest boxes:");
+    for (value, count) in &records.max_boxes {
+        println!("{:>7} {}", value, count);
+    }
+
+    Ok(())
+}
+
+/// `solve`: expectimax plays one game, the final board and score go to stdout
+fn solve(options: &Options, keep_going: bool, out: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let mut game = match options.seed {
+        Some(seed) => Game::from_seed(options.game, seed),
+        None => Game::with_config(options.game),
+    };
+    let mut strategy =
+        ai::strategy(ai::Kind::Expectimax, options.game, options.ai, game.get_seed());
+
+    game.start();
+    while game.is_alive() {
+        if game.is_won() {
+            if !keep_going {
+                break;
+            }
+            game.keep_going();
+        }
+        match strategy.next_move(&game.get_grid()) {
+            Some(cmd) => {
+                game.next_tick_untracked(cmd);
+            }
+            None => break,
         }
     }
 
-    s
+    for row in utils::format_grid(&game.get_grid()) {
+        println!("{}", row);
+    }
+    println!();
+    println!(
+        "{}: score {}, max box {}, moves {}, seed {}",
+        if game.is_target_reached() { "won" } else { "lost" },
+        game.get_score(),
+        game.get_max_box(),
+        game.get_moves(),
+        game.get_seed()
+    );
+    if let Some(path) = out {
+        fs::write(&path, game.get_replay().to_text())?;
+        println!("replay written to {}", path.display());
+    }
+
+    Ok(())
 }
 
-/// render different color for different score
-fn score_to_color(score: i32) -> Color {
-    if score < 64 {
-        Color::Green
-    } else if score < 256 {
-        Color::Magenta
-    } else if score < 1024 {
-        Color::Cyan
-    } else if score < 4096 {
-        Color::LightRed
-    } else {
-        Color::Red
+/// move actions move, other keys do nothing
+fn action_to_command(action: Option<Action>) -> Command {
+    match action {
+        Some(Action::Left) => Command::Left,
+        Some(Action::Up) => Command::Up,
+        Some(Action::Right) => Command::Right,
+        Some(Action::Down) => Command::Down,
+        _ => Command::Nil,
     }
 }
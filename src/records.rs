@@ -0,0 +1,111 @@
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, Rule};
+use crate::save::{self, SaveError};
+
+/// records file format version
+const VERSION: u64 = 1;
+
+/// records file name under the data directory
+const FILE_NAME: &str = "records.json";
+
+/// how many games the high-score table keeps
+const HIGH_SCORES: usize = 10;
+
+/// one finished game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub score: i32,
+    /// the biggest box
+    pub max_box: i32,
+    pub moves: u32,
+    /// play time in seconds
+    pub duration: u64,
+    pub rows: usize,
+    pub cols: usize,
+    pub rule: Rule,
+    /// finish time, unix seconds
+    pub date: u64,
+    /// how many times undo is used, `0` means a clean run
+    pub undos: u32,
+    /// target box is reached
+    pub won: bool,
+}
+
+impl Record {
+    /// make a record from a finished game
+    pub fn from_game(game: &Game) -> Record {
+        let config = game.get_config();
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Record {
+            score: game.get_score(),
+            max_box: game.get_max_box(),
+            moves: game.get_moves(),
+            duration: game.get_play_time().as_secs(),
+            rows: config.rows,
+            cols: config.cols,
+            rule: config.rule,
+            date,
+            undos: game.get_undos(),
+            won: game.is_target_reached(),
+        }
+    }
+}
+
+/// high-score table and lifetime statistics
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Records {
+    /// best games, highest score first
+    pub high_scores: Vec<Record>,
+    pub games_played: u32,
+    pub games_won: u32,
+    /// the biggest box of each game -> how many games
+    pub max_boxes: BTreeMap<i32, u32>,
+}
+
+impl Records {
+    /// count a finished game
+    pub fn add(&mut self, record: Record) {
+        self.games_played += 1;
+        if record.won {
+            self.games_won += 1;
+        }
+        *self.max_boxes.entry(record.max_box).or_insert(0) += 1;
+
+        let pos = self
+            .high_scores
+            .iter()
+            .position(|r| r.score < record.score)
+            .unwrap_or(self.high_scores.len());
+        self.high_scores.insert(pos, record);
+        self.high_scores.truncate(HIGH_SCORES);
+    }
+
+    /// percentage of won games
+    pub fn win_rate(&self) -> f64 {
+        if self.games_played == 0 {
+            0.0
+        } else {
+            self.games_won as f64 * 100.0 / self.games_played as f64
+        }
+    }
+}
+
+/// read records, empty records if there is no records file
+pub fn load() -> Result<Records, SaveError> {
+    Ok(save::read_file(FILE_NAME, VERSION)?.unwrap_or_default())
+}
+
+/// write records
+pub fn save(records: &Records) -> Result<(), SaveError> {
+    save::write_file(FILE_NAME, VERSION, records)
+}
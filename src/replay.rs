@@ -0,0 +1,564 @@
+//! Recorded games, a seed plus every step, saved as JSON or compact text.
+//!
+//! Text format, one key per line, then all steps:
+//!
+//! ```text
+//! tui-2048 replay 2
+//! size 4x4
+//! rule standard
+//! target 2048
+//! undo-limit 10
+//! spawn-weights 9 1 0 0
+//! spawn-per-move 1
+//! spawn-initial 2
+//! spawn-scale fixed
+//! spawn-mode random
+//! seed 42
+//! start 5 12.4
+//! steps
+//! L3 D0.4 u r U7
+//! ```
+//!
+//! A box is `cell[.value]`, `cell = row * cols + col` and value `2` is omitted.
+//! A move is `L`, `U`, `R` or `D` followed by the boxes inserted after it joined by `+`,
+//! e.g. `L3+9.4`, `u` is undo and `r` is redo.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::game::{
+    Command, Config, Game, MoveOutcome, Rule, Scale, Spawn, SpawnMode, SPAWN_VALUES,
+};
+use crate::utils;
+
+/// replay format version, bump it when the layout of `Replay` changes
+pub const VERSION: u64 = 2;
+
+/// first line of the text format, followed by the version
+const TEXT_HEADER: &str = "tui-2048 replay";
+
+/// steps per line in the text format
+const STEPS_PER_LINE: usize = 16;
+
+/// largest side of a replayed board, so a broken file can not allocate a huge grid
+const MAX_SIDE: usize = 256;
+
+/// a recorded game, same config, seed and steps make the same game
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    /// game config
+    pub config: Config,
+    /// random seed of the recorded game
+    pub seed: u64,
+    /// boxes inserted by `Game::start`
+    pub start: Vec<Spawn>,
+    /// every move, undo and redo in order
+    pub steps: Vec<Step>,
+}
+
+/// one recorded action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Step {
+    /// a move which changed the grid and the boxes inserted after it
+    Move {
+        /// the move
+        cmd: Command,
+        /// the boxes inserted after it
+        spawns: Vec<Spawn>,
+    },
+    /// take back last move
+    Undo,
+    /// redo last undone move
+    Redo,
+}
+
+/// replay errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayError {
+    /// content can not be understood, `line` starts from 1, `0` for JSON
+    Parse {
+        /// where
+        line: usize,
+        /// why
+        reason: String,
+    },
+    /// written by another version of this game
+    Version {
+        /// version in the replay
+        found: u64,
+        /// version this game reads
+        expected: u64,
+    },
+    /// a step can not be played, `step` starts from 0
+    Mismatch {
+        /// which step, steps count from 0
+        step: usize,
+        /// why
+        reason: String,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Parse { line: 0, reason } => write!(f, "invalid replay, {}", reason),
+            ReplayError::Parse { line, reason } => {
+                write!(f, "invalid replay at line {}, {}", line, reason)
+            }
+            ReplayError::Version { found, expected } => write!(
+                f,
+                "replay version {} is not supported, expected {}",
+                found, expected
+            ),
+            ReplayError::Mismatch { step, reason } => {
+                write!(f, "replay step {} can not be played, {}", step + 1, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// layout of the JSON format, `{ "version": 1, "data": ... }`, same as save files
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u64,
+    data: &'a Replay,
+}
+
+impl Replay {
+    /// game before any step
+    pub fn new_game(&self) -> Result<Game, ReplayError> {
+        let config = self.config;
+        let sides = 1..=MAX_SIDE;
+        if !sides.contains(&config.rows) || !sides.contains(&config.cols) {
+            return Err(parse_error(
+                0,
+                &format!("invalid size {}x{}", config.rows, config.cols),
+            ));
+        }
+        utils::check_target(config.target).map_err(|reason| parse_error(0, &reason))?;
+        config.spawn.validate().map_err(|reason| parse_error(0, &reason))?;
+
+        let mut game = Game::from_seed(self.config, self.seed);
+        game.start_from(&self.start)
+            .map_err(|reason| ReplayError::Parse {
+                line: 0,
+                reason: format!("bad start box, {}", reason),
+            })?;
+
+        Ok(game)
+    }
+
+    /// game after the first `n` steps
+    pub fn game_at(&self, n: usize) -> Result<Game, ReplayError> {
+        let mut game = self.new_game()?;
+        for index in 0..n.min(self.steps.len()) {
+            self.apply(&mut game, index)?;
+        }
+
+        Ok(game)
+    }
+
+    /// play step `index` on a game which has played all steps before it
+    pub fn apply(&self, game: &mut Game, index: usize) -> Result<MoveOutcome, ReplayError> {
+        let mismatch = |reason: String| ReplayError::Mismatch {
+            step: index,
+            reason,
+        };
+
+        match self.steps.get(index) {
+            Some(Step::Move { cmd, spawns }) => {
+                // moving after winning means player chose to keep going
+                if game.is_won() {
+                    game.keep_going();
+                }
+                match game.next_tick_with(*cmd, spawns) {
+                    Ok(outcome) if outcome.changed => Ok(outcome),
+                    Ok(_) => Err(mismatch(format!("{:?} moves nothing", cmd))),
+                    Err(reason) => Err(mismatch(reason)),
+                }
+            }
+            Some(Step::Undo) if game.undo() => Ok(MoveOutcome::default()),
+            Some(Step::Undo) => Err(mismatch("nothing to undo".to_string())),
+            Some(Step::Redo) if game.redo() => Ok(MoveOutcome::default()),
+            Some(Step::Redo) => Err(mismatch("nothing to redo".to_string())),
+            None => Err(mismatch("no such step".to_string())),
+        }
+    }
+
+    /// play every step, so a broken replay is found before watching it
+    pub fn validate(&self) -> Result<(), ReplayError> {
+        self.game_at(self.steps.len()).map(|_| ())
+    }
+
+    /// JSON format
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&Versioned {
+            version: VERSION,
+            data: self,
+        })
+        .expect("replay is always serializable")
+    }
+
+    /// read JSON format
+    pub fn from_json(content: &str) -> Result<Replay, ReplayError> {
+        let parse = |reason: String| ReplayError::Parse { line: 0, reason };
+        let mut value: Value = serde_json::from_str(content).map_err(|e| parse(e.to_string()))?;
+
+        match value.get("version").and_then(Value::as_u64) {
+            Some(VERSION) => {}
+            Some(found) => {
+                return Err(ReplayError::Version {
+                    found,
+                    expected: VERSION,
+                })
+            }
+            None => return Err(parse("missing version".to_string())),
+        }
+
+        serde_json::from_value(value["data"].take()).map_err(|e| parse(e.to_string()))
+    }
+
+    /// compact text format
+    pub fn to_text(&self) -> String {
+        let config = self.config;
+        let cols = config.cols;
+        let spawn = config.spawn;
+        let weights: Vec<String> = spawn.weights.iter().map(|w| w.to_string()).collect();
+        let mut text = format!(
+            "{} {}\nsize {}x{}\nrule {}\ntarget {}\nundo-limit {}\n",
+            TEXT_HEADER, VERSION, config.rows, cols, config.rule, config.target, config.undo_limit,
+        );
+        text.push_str(&format!(
+            "spawn-weights {}\nspawn-per-move {}\nspawn-initial {}\nspawn-scale {}\nspawn-mode {}\n\
+             seed {}\n",
+            weights.join(" "),
+            spawn.per_move,
+            spawn.initial,
+            spawn.scale,
+            spawn.mode,
+            self.seed
+        ));
+
+        let start: Vec<String> = self.start.iter().map(|s| encode_spawn(s, cols)).collect();
+        text.push_str(&format!("start {}\nsteps\n", start.join(" ")));
+
+        let steps: Vec<String> = self.steps.iter().map(|s| encode_step(s, cols)).collect();
+        for line in steps.chunks(STEPS_PER_LINE) {
+            text.push_str(&line.join(" "));
+            text.push('\n');
+        }
+
+        text
+    }
+
+    /// read compact text format
+    pub fn from_text(content: &str) -> Result<Replay, ReplayError> {
+        let mut lines = content.lines().enumerate().map(|(i, line)| (i + 1, line.trim()));
+
+        match lines.next() {
+            Some((_, first)) if first.starts_with(TEXT_HEADER) => {
+                let version = &first[TEXT_HEADER.len()..];
+                match version.trim().parse() {
+                    Ok(VERSION) => {}
+                    Ok(found) => {
+                        return Err(ReplayError::Version {
+                            found,
+                            expected: VERSION,
+                        })
+                    }
+                    Err(_) => return Err(parse_error(1, "missing version")),
+                }
+            }
+            _ => return Err(parse_error(1, "not a replay")),
+        }
+
+        let mut config = Config::default();
+        let mut seed = None;
+        let mut start = (0, "");
+        for (line, text) in lines.by_ref() {
+            let (key, value) = text.split_once(' ').unwrap_or((text, ""));
+            let value = value.trim();
+            let invalid = |what: &str| parse_error(line, &format!("invalid {} `{}`", what, value));
+            match key {
+                "" => {}
+                "size" => {
+                    let (rows, cols) = utils::parse_size(value, 1, MAX_SIDE)
+                        .map_err(|reason| parse_error(line, &reason))?;
+                    config.rows = rows;
+                    config.cols = cols;
+                }
+                "rule" => {
+                    config.rule = value
+                        .parse::<Rule>()
+                        .map_err(|reason| parse_error(line, &reason))?
+                }
+                "target" => {
+                    config.target =
+                        utils::parse_target(value).map_err(|reason| parse_error(line, &reason))?
+                }
+                "undo-limit" => {
+                    config.undo_limit = value.parse().map_err(|_| invalid("undo limit"))?
+                }
+                "spawn-weights" => {
+                    let weights = value
+                        .split_whitespace()
+                        .map(|weight| weight.parse().map_err(|_| invalid("spawn weights")))
+                        .collect::<Result<Vec<u32>, _>>()?;
+                    if weights.len() != SPAWN_VALUES.len() {
+                        return Err(invalid("spawn weights"));
+                    }
+                    config.spawn.weights.copy_from_slice(&weights);
+                }
+                "spawn-per-move" => {
+                    config.spawn.per_move = value.parse().map_err(|_| invalid("spawn count"))?
+                }
+                "spawn-initial" => {
+                    config.spawn.initial = value.parse().map_err(|_| invalid("start count"))?
+                }
+                "spawn-scale" => {
+                    config.spawn.scale = value
+                        .parse::<Scale>()
+                        .map_err(|reason| parse_error(line, &reason))?
+                }
+                "spawn-mode" => {
+                    config.spawn.mode = value
+                        .parse::<SpawnMode>()
+                        .map_err(|reason| parse_error(line, &reason))?
+                }
+                "seed" => seed = Some(value.parse().map_err(|_| invalid("seed"))?),
+                "start" => start = (line, value),
+                "steps" => break,
+                _ => return Err(parse_error(line, &format!("unknown key `{}`", key))),
+            }
+        }
+
+        let seed = seed.ok_or_else(|| parse_error(0, "missing seed"))?;
+        let cols = config.cols;
+        let start = start
+            .1
+            .split_whitespace()
+            .map(|token| decode_spawn(token, cols).ok_or_else(|| bad_token(start.0, token)))
+            .collect::<Result<_, _>>()?;
+
+        let mut steps = vec![];
+        for (line, text) in lines {
+            for token in text.split_whitespace() {
+                steps.push(decode_step(token, cols).ok_or_else(|| bad_token(line, token))?);
+            }
+        }
+
+        Ok(Replay {
+            config,
+            seed,
+            start,
+            steps,
+        })
+    }
+
+    /// read either format, JSON starts with `{`
+    pub fn parse(content: &str) -> Result<Replay, ReplayError> {
+        if content.trim_start().starts_with('{') {
+            Replay::from_json(content)
+        } else {
+            Replay::from_text(content)
+        }
+    }
+}
+
+fn parse_error(line: usize, reason: &str) -> ReplayError {
+    ReplayError::Parse {
+        line,
+        reason: reason.to_string(),
+    }
+}
+
+fn bad_token(line: usize, token: &str) -> ReplayError {
+    parse_error(line, &format!("invalid step `{}`", token))
+}
+
+/// `cell[.value]`, value `2` is omitted
+fn encode_spawn(spawn: &Spawn, cols: usize) -> String {
+    let cell = spawn.cell.0 * cols + spawn.cell.1;
+    if spawn.value == 2 {
+        cell.to_string()
+    } else {
+        format!("{}.{}", cell, spawn.value)
+    }
+}
+
+fn decode_spawn(token: &str, cols: usize) -> Option<Spawn> {
+    let (cell, value) = match token.split_once('.') {
+        Some((cell, value)) => (cell.parse::<usize>().ok()?, value.parse().ok()?),
+        None => (token.parse::<usize>().ok()?, 2),
+    };
+
+    Some(Spawn {
+        cell: (cell / cols.max(1), cell % cols.max(1)),
+        value,
+    })
+}
+
+/// `L`, `U`, `R` or `D` followed by the inserted boxes, `u` for undo, `r` for redo
+fn encode_step(step: &Step, cols: usize) -> String {
+    match step {
+        Step::Move { cmd, spawns } => {
+            let letter = match cmd {
+                Command::Left => 'L',
+                Command::Up => 'U',
+                Command::Right => 'R',
+                Command::Down => 'D',
+                Command::Nil => 'N',
+            };
+            let spawns: Vec<String> = spawns.iter().map(|s| encode_spawn(s, cols)).collect();
+            format!("{}{}", letter, spawns.join("+"))
+        }
+        Step::Undo => "u".to_string(),
+        Step::Redo => "r".to_string(),
+    }
+}
+
+fn decode_step(token: &str, cols: usize) -> Option<Step> {
+    let cmd = match token.chars().next()? {
+        'u' if token.len() == 1 => return Some(Step::Undo),
+        'r' if token.len() == 1 => return Some(Step::Redo),
+        'L' => Command::Left,
+        'U' => Command::Up,
+        'R' => Command::Right,
+        'D' => Command::Down,
+        _ => return None,
+    };
+
+    let spawns = match &token[1..] {
+        "" => vec![],
+        spawns => spawns
+            .split('+')
+            .map(|spawn| decode_spawn(spawn, cols))
+            .collect::<Option<_>>()?,
+    };
+
+    Some(Step::Move { cmd, spawns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Grid;
+
+    /// a 3x5 game with `4`s, undo and redo, and the grid and score after each step
+    fn recorded() -> (Replay, Vec<(Grid, i32)>) {
+        let mut config = Config {
+            rows: 3,
+            cols: 5,
+            ..Config::default()
+        };
+        config.spawn.weights = [1, 1, 0, 0];
+        let mut game = Game::from_seed(config, 7);
+        game.start();
+
+        let mut states = vec![(game.get_grid(), game.get_score())];
+        for i in 0..30 {
+            match i % 10 {
+                4 => assert!(game.undo()),
+                5 => assert!(game.redo()),
+                _ => match game.get_legal_moves().first() {
+                    Some(cmd) => {
+                        game.next_tick(*cmd);
+                    }
+                    None => break,
+                },
+            }
+            states.push((game.get_grid(), game.get_score()));
+        }
+
+        (game.get_replay(), states)
+    }
+
+    #[test]
+    fn formats_round_trip() {
+        let (replay, _) = recorded();
+        assert!(replay.steps.contains(&Step::Undo) && replay.steps.contains(&Step::Redo));
+        let spawns = replay.steps.iter().flat_map(|step| match step {
+            Step::Move { spawns, .. } => spawns.clone(),
+            _ => vec![],
+        });
+        assert!(spawns.chain(replay.start.clone()).any(|spawn| spawn.value != 2));
+
+        assert_eq!(Replay::from_text(&replay.to_text()), Ok(replay.clone()));
+        assert_eq!(Replay::from_json(&replay.to_json()), Ok(replay.clone()));
+        assert_eq!(Replay::parse(&replay.to_json()), Ok(replay.clone()));
+        assert_eq!(Replay::parse(&replay.to_text()), Ok(replay));
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        let (replay, _) = recorded();
+        let expected = Err(ReplayError::Version {
+            found: 1,
+            expected: VERSION,
+        });
+
+        let text = replay.to_text();
+        let text = text.replacen(&format!("{} {}", TEXT_HEADER, VERSION), "tui-2048 replay 1", 1);
+        assert_eq!(Replay::from_text(&text), expected);
+
+        let json = replay
+            .to_json()
+            .replacen(&format!("\"version\":{}", VERSION), "\"version\":1", 1);
+        assert_eq!(Replay::from_json(&json), expected);
+    }
+
+    #[test]
+    fn spawn_on_a_taken_cell_is_a_mismatch() {
+        let (mut replay, _) = recorded();
+        let grid = replay.game_at(1).unwrap().get_grid();
+        let spawns = match &mut replay.steps[0] {
+            Step::Move { spawns, .. } => spawns,
+            step => panic!("first step is {:?}", step),
+        };
+        let taken = (0..grid.len())
+            .flat_map(|i| (0..grid[i].len()).map(move |j| (i, j)))
+            .find(|&(i, j)| grid[i][j] != 0 && spawns.iter().all(|s| s.cell != (i, j)))
+            .unwrap();
+        spawns[0].cell = taken;
+
+        assert!(matches!(
+            replay.validate(),
+            Err(ReplayError::Mismatch { step: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn oversized_boxes_are_refused() {
+        let (mut replay, _) = recorded();
+        replay.start[0].value = 1 << 30;
+        assert!(matches!(replay.validate(), Err(ReplayError::Parse { .. })));
+    }
+
+    #[test]
+    fn targets_are_checked() {
+        let (mut replay, _) = recorded();
+        for target in ["0", "-5", "6"] {
+            let text = replay.to_text().replacen("target 2048", &format!("target {}", target), 1);
+            assert!(matches!(Replay::from_text(&text), Err(ReplayError::Parse { line: 4, .. })));
+        }
+
+        replay.config.target = 0;
+        let json = Replay::from_json(&replay.to_json()).unwrap();
+        assert!(matches!(json.validate(), Err(ReplayError::Parse { .. })));
+    }
+
+    #[test]
+    fn game_at_matches_the_live_game() {
+        let (replay, states) = recorded();
+        assert_eq!(states.len(), replay.steps.len() + 1);
+        for (n, (grid, score)) in states.iter().enumerate() {
+            let game = replay.game_at(n).unwrap();
+            assert_eq!(&game.get_grid(), grid, "after {} steps", n);
+            assert_eq!(game.get_score(), *score, "after {} steps", n);
+        }
+    }
+}
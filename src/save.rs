@@ -0,0 +1,186 @@
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::game::Game;
+use crate::replay::{Replay, ReplayError};
+use crate::utils;
+
+/// save file format version, bump it when the layout of `Game` changes
+const VERSION: u64 = 1;
+
+/// save file name under the data directory
+const FILE_NAME: &str = "save.json";
+
+/// replays of finished games under the data directory
+const REPLAY_DIR: &str = "replays";
+
+/// layout of every data file, `{ "version": 1, "data": ... }`
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    version: u64,
+    data: &'a T,
+}
+
+/// save/load errors
+#[derive(Debug)]
+pub enum SaveError {
+    /// no data directory on this platform
+    NoDataDir,
+    /// read/write failed
+    Io(PathBuf, io::Error),
+    /// file content can not be understood
+    Corrupt(PathBuf, String),
+    /// file is written by another version of this game
+    Version {
+        path: PathBuf,
+        found: u64,
+        expected: u64,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::NoDataDir => write!(f, "can not find a data directory for save files"),
+            SaveError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            SaveError::Corrupt(path, reason) => {
+                write!(f, "{}: file is corrupt, {}", path.display(), reason)
+            }
+            SaveError::Version {
+                path,
+                found,
+                expected,
+            } => write!(
+                f,
+                "{}: file version {} is not supported, expected {}",
+                path.display(),
+                found,
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// data directory of this game, `$XDG_DATA_HOME/tui-2048` or `~/.local/share/tui-2048`
+pub fn data_dir() -> Result<PathBuf, SaveError> {
+    dirs::data_dir()
+        .map(|dir| dir.join("tui-2048"))
+        .ok_or(SaveError::NoDataDir)
+}
+
+/// write game into save file
+pub fn save(game: &Game) -> Result<(), SaveError> {
+    write_file(FILE_NAME, VERSION, game)
+}
+
+/// read game from save file, `None` if there is no save file
+pub fn load() -> Result<Option<Game>, SaveError> {
+    let game: Option<Game> = read_file(FILE_NAME, VERSION)?;
+
+    if let Some(game) = &game {
+        if let Err(reason) = game.validate() {
+            return Err(SaveError::Corrupt(data_dir()?.join(FILE_NAME), reason));
+        }
+    }
+
+    Ok(game)
+}
+
+/// remove save file, e.g. the saved game is over
+pub fn remove() -> Result<(), SaveError> {
+    let path = data_dir()?.join(FILE_NAME);
+
+    match fs::remove_file(&path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(SaveError::Io(path, err)),
+        _ => Ok(()),
+    }
+}
+
+/// write the replay of a finished game in text format, returns the file path
+///
+/// `date` is unix seconds, files are named `<date>-<score>-<seed>.replay`
+pub fn save_replay(replay: &Replay, score: i32, date: u64) -> Result<PathBuf, SaveError> {
+    let name = format!("{}-{}-{}.replay", utils::format_date(date), score, replay.seed);
+    let path = data_dir()?.join(REPLAY_DIR).join(name);
+    write_atomic(&path, &replay.to_text())?;
+
+    Ok(path)
+}
+
+/// read a replay file in JSON or text format, every step is checked
+pub fn load_replay(path: &Path) -> Result<Replay, SaveError> {
+    let content = fs::read_to_string(path).map_err(|err| SaveError::Io(path.to_path_buf(), err))?;
+
+    Replay::parse(&content)
+        .and_then(|replay| replay.validate().map(|_| replay))
+        .map_err(|err| match err {
+            ReplayError::Version { found, expected } => SaveError::Version {
+                path: path.to_path_buf(),
+                found,
+                expected,
+            },
+            err => SaveError::Corrupt(path.to_path_buf(), err.to_string()),
+        })
+}
+
+/// write versioned data into a file under the data directory
+pub fn write_file<T: Serialize>(name: &str, version: u64, data: &T) -> Result<(), SaveError> {
+    let path = data_dir()?.join(name);
+
+    let content = serde_json::to_string(&Versioned { version, data })
+        .map_err(|err| SaveError::Corrupt(path.clone(), err.to_string()))?;
+
+    write_atomic(&path, &content)
+}
+
+/// write a temporary file first, so a crash never leaves half a file
+fn write_atomic(path: &Path, content: &str) -> Result<(), SaveError> {
+    let io_err = |err| SaveError::Io(path.to_path_buf(), err);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_err)?;
+    }
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, content).map_err(io_err)?;
+    fs::rename(&tmp, path).map_err(io_err)
+}
+
+/// read versioned data from a file under the data directory, `None` if there is no such file
+pub fn read_file<T: DeserializeOwned>(name: &str, version: u64) -> Result<Option<T>, SaveError> {
+    let path = data_dir()?.join(name);
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(SaveError::Io(path, err)),
+    };
+
+    let mut value: Value = match serde_json::from_str(&content) {
+        Ok(value) => value,
+        Err(err) => return Err(SaveError::Corrupt(path, err.to_string())),
+    };
+
+    match value.get("version").and_then(Value::as_u64) {
+        Some(found) if found == version => {}
+        Some(found) => {
+            return Err(SaveError::Version {
+                path,
+                found,
+                expected: version,
+            })
+        }
+        None => return Err(SaveError::Corrupt(path, "missing version".to_string())),
+    }
+
+    serde_json::from_value(value["data"].take())
+        .map(Some)
+        .map_err(|err| SaveError::Corrupt(path, err.to_string()))
+}
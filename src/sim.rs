@@ -0,0 +1,301 @@
+//! Play many games without the TUI and summarize them.
+
+use std::{
+    collections::BTreeMap,
+    fmt, thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::ai::{self, Kind};
+use crate::game::{self, Game};
+use crate::utils;
+
+/// usage text of the simulation arguments
+pub const USAGE: &str = "Usage: simulate [--games <number>] [--strategy random|corner|expectimax] \
+                         [--size <rows>x<cols>] [--rule standard|chain] [--target <number>] \
+                         [--spawn-weights <2,4,8,16>] [--spawn-per-move <number>] \
+                         [--spawn-initial <number>] \
+                         [--spawn-scale fixed|max-box/<number>] [--spawn-mode random|evil|kind] \
+                         [--seed <number>] [--threads <number>] \
+                         [--ai-depth <number>] [--ai-budget <ms>] [--json]";
+
+/// simulation config
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// how many games to play
+    pub games: u32,
+    /// who plays
+    pub strategy: Kind,
+    /// game config, undo is always disabled
+    pub game: game::Config,
+    /// search config for expectimax
+    pub ai: ai::Config,
+    /// game `i` uses seed `seed + i`
+    pub seed: u64,
+    /// worker threads
+    pub threads: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            games: 1000,
+            strategy: Kind::Corner,
+            game: game::Config::default(),
+            ai: ai::Config::default(),
+            seed: 0,
+            threads: 1,
+        }
+    }
+}
+
+/// result of one game
+#[derive(Debug, Clone, Copy)]
+struct Outcome {
+    score: i32,
+    max_box: i32,
+    moves: u32,
+    won: bool,
+}
+
+/// distribution of a value over all games
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    /// smallest value
+    pub min: f64,
+    /// average value
+    pub mean: f64,
+    /// 50th percentile
+    pub median: f64,
+    /// 90th percentile
+    pub p90: f64,
+    /// biggest value
+    pub max: f64,
+}
+
+impl Summary {
+    fn new(mut values: Vec<f64>) -> Summary {
+        if values.is_empty() {
+            values.push(0.0);
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+        Summary {
+            min: values[0],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            median: percentile(0.5),
+            p90: percentile(0.9),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// simulation report
+#[derive(Debug, Serialize)]
+pub struct Report {
+    /// games played
+    pub games: u32,
+    /// who played
+    pub strategy: String,
+    /// grid rows
+    pub rows: usize,
+    /// grid columns
+    pub cols: usize,
+    /// merge rule
+    pub rule: String,
+    /// seed of the first game
+    pub seed: u64,
+    /// games which reached the target box
+    pub wins: u32,
+    /// merge points of each game
+    pub score: Summary,
+    /// moves of each game
+    pub moves: Summary,
+    /// the biggest box of each game -> how many games
+    pub max_box: BTreeMap<i32, u32>,
+    /// wall time of the whole run
+    pub seconds: f64,
+    /// throughput of the whole run
+    pub games_per_second: f64,
+}
+
+/// play all games and summarize them
+pub fn run(config: &Config) -> Report {
+    let started = Instant::now();
+    let threads = config.threads.max(1);
+
+    let outcomes: Vec<Outcome> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|t| {
+                scope.spawn(move || {
+                    (0..config.games)
+                        .filter(|i| *i as usize % threads == t)
+                        .map(|i| play(config, config.seed.wrapping_add(i as u64)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("simulation worker panicked"))
+            .collect()
+    });
+
+    let seconds = started.elapsed().as_secs_f64();
+    let mut max_box = BTreeMap::new();
+    for outcome in &outcomes {
+        *max_box.entry(outcome.max_box).or_insert(0) += 1;
+    }
+
+    Report {
+        games: outcomes.len() as u32,
+        strategy: config.strategy.to_string(),
+        rows: config.game.rows,
+        cols: config.game.cols,
+        rule: config.game.rule.to_string(),
+        seed: config.seed,
+        wins: outcomes.iter().filter(|o| o.won).count() as u32,
+        score: Summary::new(outcomes.iter().map(|o| o.score as f64).collect()),
+        moves: Summary::new(outcomes.iter().map(|o| o.moves as f64).collect()),
+        max_box,
+        seconds,
+        games_per_second: if seconds > 0.0 {
+            outcomes.len() as f64 / seconds
+        } else {
+            0.0
+        },
+    }
+}
+
+/// play one game until it is over, keep going after winning
+fn play(config: &Config, seed: u64) -> Outcome {
+    let game_config = game::Config {
+        undo_limit: 0,
+        ..config.game
+    };
+    let mut game = Game::from_seed(game_config, seed);
+    let mut strategy = ai::strategy(config.strategy, game_config, config.ai, seed);
+
+    game.start();
+    while game.is_alive() {
+        if game.is_won() {
+            game.keep_going();
+        }
+        match strategy.next_move(&game.get_grid()) {
+            Some(cmd) => {
+                game.next_tick_untracked(cmd);
+            }
+            None => break,
+        }
+    }
+
+    Outcome {
+        score: game.get_score(),
+        max_box: game.get_max_box(),
+        moves: game.get_moves(),
+        won: game.is_target_reached(),
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} games, {} strategy, {}x{} board, {} rule, seed {}",
+            self.games, self.strategy, self.rows, self.cols, self.rule, self.seed
+        )?;
+        writeln!(
+            f,
+            "{:.2}s, {:.1} games/s, {} wins ({:.1}%)",
+            self.seconds,
+            self.games_per_second,
+            self.wins,
+            self.wins as f64 * 100.0 / self.games.max(1) as f64
+        )?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<8}{:>10}{:>10}{:>10}{:>10}{:>10}",
+            "", "min", "mean", "median", "p90", "max"
+        )?;
+        for (name, s) in [("score", &self.score), ("moves", &self.moves)] {
+            writeln!(
+                f,
+                "{:<8}{:>10.0}{:>10.1}{:>10.0}{:>10.0}{:>10.0}",
+                name, s.min, s.mean, s.median, s.p90, s.max
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "{:<8}{:>10}{:>10}", "max box", "games", "share")?;
+        for (num, count) in self.max_box.iter().rev() {
+            writeln!(
+                f,
+                "{:<8}{:>10}{:>9.1}%",
+                num,
+                count,
+                *count as f64 * 100.0 / self.games.max(1) as f64
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// parse simulation arguments, program name excluded, returns config and whether to print JSON
+pub fn parse_args<I>(args: I) -> Result<(Config, bool), String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut config = Config {
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        ..Default::default()
+    };
+    // searching is slow, a deeper budget is fine without a player waiting
+    config.ai.depth = 2;
+    config.ai.time_budget = Duration::from_secs(1);
+    let mut json = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("`{}` expects a value", arg));
+        match arg.as_str() {
+            "--games" => config.games = number(&value()?, "games")?,
+            "--strategy" => config.strategy = value()?.parse()?,
+            "--size" => {
+                let (rows, cols) = utils::parse_size(&value()?, utils::MIN_SIDE, utils::MAX_SIDE)?;
+                config.game.rows = rows;
+                config.game.cols = cols;
+            }
+            "--rule" => config.game.rule = value()?.parse()?,
+            "--target" => config.game.target = utils::parse_target(&value()?)?,
+            "--seed" => config.seed = number(&value()?, "seed")?,
+            "--spawn-weights" => config.game.spawn.weights = utils::parse_weights(&value()?)?,
+            "--spawn-per-move" => config.game.spawn.per_move = number(&value()?, "spawn count")?,
+            "--spawn-initial" => config.game.spawn.initial = number(&value()?, "start count")?,
+            "--spawn-scale" => config.game.spawn.scale = value()?.parse()?,
+            "--spawn-mode" => config.game.spawn.mode = value()?.parse()?,
+            "--threads" => config.threads = number(&value()?, "threads")?,
+            "--ai-depth" => config.ai.depth = number(&value()?, "AI depth")?,
+            "--ai-budget" => {
+                config.ai.time_budget = Duration::from_millis(number(&value()?, "AI budget")?)
+            }
+            "--json" => json = true,
+            _ => return Err(format!("unknown argument `{}`", arg)),
+        }
+    }
+
+    config.game.spawn.validate()?;
+
+    Ok((config, json))
+}
+
+/// parse a number argument
+fn number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} `{}`", name, value))
+}
@@ -0,0 +1,371 @@
+use std::{
+    error::Error,
+    io::{BufRead, Write},
+    time::Instant,
+};
+
+use termion::{event::Key, input::TermRead};
+
+use crate::ai::Expectimax;
+use crate::config::{self, Action, Keys, Settings};
+use crate::game::{Command, Game, MoveOutcome};
+use crate::records::{self, Record};
+use crate::save;
+use crate::utils;
+
+/// line-oriented play for screen readers, `tui-2048 text`
+///
+/// Rules:
+///
+/// 1. nothing is drawn and the screen is left alone, every answer is whole lines of plain text
+/// 2. input is words, a word is a key name like `left` or `ctrl-r`, an action name like `undo`,
+///    or keys typed together like `hhj`, an empty line reads the board again
+/// 3. keys do what they do in the terminal UI, see `config::Keys`
+/// 4. after each move: the board row by row, what merged, where new boxes are and the score
+/// 5. a finished game is recorded like in the terminal UI, an unfinished one is not saved
+pub struct Text<'a, W: Write> {
+    game: Game,
+    settings: &'a Settings,
+    out: W,
+    /// play time is counted from the last answer
+    since: Instant,
+}
+
+impl<'a, W: Write> Text<'a, W> {
+    pub fn new(settings: &'a Settings, out: W) -> Text<'a, W> {
+        let options = &settings.options;
+        let mut game = match options.seed {
+            Some(seed) => Game::from_seed(options.game, seed),
+            None => Game::with_config(options.game),
+        };
+        game.start();
+
+        Text {
+            game,
+            settings,
+            out,
+            since: Instant::now(),
+        }
+    }
+
+    /// answer every line of `input` until quit or end of input
+    pub fn run<R: BufRead>(&mut self, input: R) -> Result<(), Box<dyn Error>> {
+        let config = self.game.get_config();
+        writeln!(
+            self.out,
+            "2048 in plain text, {} rows by {} columns, reach {}. Type keys or action names \
+             and press enter, `help` lists them.",
+            config.rows, config.cols, config.target
+        )?;
+        self.describe_board()?;
+
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                self.describe_board()?;
+                continue;
+            }
+            for word in line.split_whitespace() {
+                let actions = match self.parse_word(word) {
+                    Ok(actions) => actions,
+                    Err(reason) => {
+                        writeln!(self.out, "{}, `help` lists the keys.", reason)?;
+                        break;
+                    }
+                };
+                for action in actions {
+                    if !self.act(action)? {
+                        return self.quit();
+                    }
+                }
+            }
+        }
+
+        self.quit()
+    }
+
+    /// actions of one word, `None` for `help`
+    fn parse_word(&self, word: &str) -> Result<Vec<Option<Action>>, String> {
+        let keys = &self.settings.keys;
+        if word == "help" {
+            return Ok(vec![None]);
+        }
+        if let Ok(key) = config::parse_key(word) {
+            return Ok(vec![Some(key_action(keys, key, word)?)]);
+        }
+        if let Some(action) = Action::named(word) {
+            return Ok(vec![Some(action)]);
+        }
+        // arrow keys arrive as escape sequences
+        word.as_bytes()
+            .keys()
+            .map(|key| {
+                let key = key.map_err(|_| format!("unknown key in `{}`", word))?;
+                key_action(keys, key, word).map(Some)
+            })
+            .collect()
+    }
+
+    /// do one action and describe what happened
+    ///
+    /// returns: `false` to quit
+    fn act(&mut self, action: Option<Action>) -> Result<bool, Box<dyn Error>> {
+        let action = match action {
+            Some(action) => action,
+            None => {
+                self.describe_keys()?;
+                return Ok(true);
+            }
+        };
+
+        let playing = self.game.is_alive() && !self.game.is_won();
+        match action {
+            Action::Quit => return Ok(false),
+            Action::Left | Action::Up | Action::Right | Action::Down if playing => {
+                let cmd = move_command(action);
+                let outcome = self.game.next_tick(cmd);
+                self.describe_move(cmd, &outcome)?;
+            }
+            Action::Undo | Action::Redo if playing => {
+                let done = match action {
+                    Action::Undo => self.game.undo(),
+                    _ => self.game.redo(),
+                };
+                if done {
+                    writeln!(self.out, "{} done.", capitalize(config::action_name(action)))?;
+                    self.describe_board()?;
+                } else {
+                    writeln!(self.out, "Nothing to {}.", config::action_name(action))?;
+                }
+            }
+            Action::Hint if playing => {
+                let config = self.game.get_config();
+                let best = Expectimax::new(self.settings.options.ai, config.rule, config.spawn)
+                    .best_move(&self.game.get_grid());
+                match best {
+                    Some(cmd) => writeln!(self.out, "Hint: {}.", command_name(cmd))?,
+                    None => writeln!(self.out, "No hint.")?,
+                }
+            }
+            Action::KeepGoing if self.game.is_won() => {
+                self.game.keep_going();
+                writeln!(self.out, "Keep going.")?;
+                self.describe_board()?;
+            }
+            Action::Restart if !playing => {
+                self.finish();
+                self.game = Game::with_config(self.game.get_config());
+                self.game.start();
+                writeln!(self.out, "New game.")?;
+                self.describe_board()?;
+            }
+            Action::Stats => match records::load() {
+                Ok(records) => writeln!(
+                    self.out,
+                    "Games played {}, won {}, best score {}.",
+                    records.games_played,
+                    records.games_won,
+                    records.high_scores.first().map_or(0, |r| r.score)
+                )?,
+                Err(err) => writeln!(self.out, "Records unavailable, {}.", err)?,
+            },
+            _ => {
+                writeln!(self.out, "{} does nothing now.", capitalize(config::action_name(action)))?;
+                self.describe_status()?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// the board, what merged, new boxes and the score
+    fn describe_move(&mut self, cmd: Command, outcome: &MoveOutcome) -> Result<(), Box<dyn Error>> {
+        if !outcome.changed {
+            writeln!(self.out, "{} does not move anything.", capitalize(command_name(cmd)))?;
+            return Ok(());
+        }
+
+        writeln!(self.out, "Moved {}.", command_name(cmd))?;
+        self.describe_grid()?;
+        let merged: Vec<String> = outcome
+            .merges
+            .iter()
+            .map(|(cell, value)| format!("{} at {}", value, cell_name(*cell)))
+            .collect();
+        if merged.is_empty() {
+            writeln!(self.out, "Nothing merged.")?;
+        } else {
+            writeln!(self.out, "Merged: {}.", merged.join(", "))?;
+        }
+        let spawned: Vec<String> = outcome
+            .spawns
+            .iter()
+            .map(|spawn| format!("{} at {}", spawn.value, cell_name(spawn.cell)))
+            .collect();
+        if !spawned.is_empty() {
+            writeln!(self.out, "New: {}.", spawned.join(", "))?;
+        }
+        writeln!(
+            self.out,
+            "Score {}, plus {}. Moves {}.",
+            self.game.get_score(),
+            outcome.points,
+            self.game.get_moves()
+        )?;
+
+        self.describe_status()
+    }
+
+    /// the board and the score
+    fn describe_board(&mut self) -> Result<(), Box<dyn Error>> {
+        self.describe_grid()?;
+        writeln!(
+            self.out,
+            "Score {}. Moves {}.",
+            self.game.get_score(),
+            self.game.get_moves()
+        )?;
+        self.describe_status()
+    }
+
+    /// the board row by row, `.` is empty
+    fn describe_grid(&mut self) -> Result<(), Box<dyn Error>> {
+        for (i, row) in utils::format_grid(&self.game.get_grid()).iter().enumerate() {
+            writeln!(self.out, "Row {}: {}", i + 1, row.trim())?;
+        }
+        Ok(())
+    }
+
+    /// won or over, with what to do next
+    fn describe_status(&mut self) -> Result<(), Box<dyn Error>> {
+        let keys = &self.settings.keys;
+        if !self.game.is_alive() {
+            writeln!(
+                self.out,
+                "Game over, final score {}. New game: {}. Quit: {}.",
+                self.game.get_score(),
+                keys.names(Action::Restart),
+                keys.names(Action::Quit)
+            )?;
+        } else if self.game.is_won() {
+            writeln!(
+                self.out,
+                "You win! Keep going: {}. New game: {}.",
+                keys.names(Action::KeepGoing),
+                keys.names(Action::Restart)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// every action with its keys
+    fn describe_keys(&mut self) -> Result<(), Box<dyn Error>> {
+        let keys = &self.settings.keys;
+        let ignored = [Action::Autoplay, Action::Resume, Action::Discard];
+        let bound: Vec<String> = Action::ALL
+            .iter()
+            .filter(|(action, _)| !ignored.contains(action))
+            .map(|(action, name)| format!("{}: {}", name, keys.names(*action)))
+            .collect();
+        writeln!(self.out, "Keys, or type the action name. {}.", bound.join("; "))?;
+        writeln!(self.out, "An empty line reads the board again.")?;
+        Ok(())
+    }
+
+    /// record the game and save its replay, like the terminal UI does
+    fn finish(&mut self) {
+        self.game.add_play_time(self.since.elapsed());
+        self.since = Instant::now();
+
+        let record = Record::from_game(&self.game);
+        let date = record.date;
+        let saved = records::load().and_then(|mut records| {
+            records.add(record);
+            records::save(&records)?;
+            save::save_replay(&self.game.get_replay(), self.game.get_score(), date)
+        });
+        // output is best effort here, the game goes on either way
+        let _ = match saved {
+            Ok(path) => writeln!(self.out, "Replay saved to {}.", path.display()),
+            Err(err) => writeln!(self.out, "Failed to save records, {}.", err),
+        };
+    }
+
+    /// record the game if it is over
+    fn quit(&mut self) -> Result<(), Box<dyn Error>> {
+        if !self.game.is_alive() {
+            self.finish();
+        }
+        writeln!(self.out, "Bye, score {}.", self.game.get_score())?;
+        Ok(())
+    }
+}
+
+/// what `key` does, errors name the word it came from
+fn key_action(keys: &Keys, key: Key, word: &str) -> Result<Action, String> {
+    keys.action(key)
+        .ok_or_else(|| format!("`{}` is not bound to anything", word))
+}
+
+fn move_command(action: Action) -> Command {
+    match action {
+        Action::Left => Command::Left,
+        Action::Up => Command::Up,
+        Action::Right => Command::Right,
+        _ => Command::Down,
+    }
+}
+
+fn command_name(cmd: Command) -> &'static str {
+    match cmd {
+        Command::Left => "left",
+        Command::Up => "up",
+        Command::Right => "right",
+        Command::Down => "down",
+        Command::Nil => "nothing",
+    }
+}
+
+/// `row 1 column 3`, counted from the top left
+fn cell_name((row, col): (usize, usize)) -> String {
+    format!("row {} column {}", row + 1, col + 1)
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_are_described_in_plain_text() {
+        let mut settings = Settings::default();
+        settings.options.seed = Some(42);
+        let mut out = vec![];
+        let mut text = Text::new(&settings, &mut out);
+        text.run("\x1b[D\nhelp xyz\n".as_bytes()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let mut game = Game::from_seed(settings.options.game, 42);
+        game.start();
+        let outcome = game.next_tick(Command::Left);
+        for (i, row) in utils::format_grid(&game.get_grid()).iter().enumerate() {
+            assert!(out.contains(&format!("Row {}: {}\n", i + 1, row.trim())));
+        }
+        if let Some(spawn) = outcome.spawns.first() {
+            assert!(out.contains(&format!("New: {} at {}", spawn.value, cell_name(spawn.cell))));
+        }
+        assert!(out.contains("Moved left.\n"));
+        assert!(out.contains("undo: u; redo: ctrl-r;"));
+        assert!(out.contains("`xyz` is not bound to anything"));
+        assert!(out.ends_with(&format!("Bye, score {}.\n", game.get_score())));
+        // plain text only, no escape sequences for the screen
+        assert!(!out.contains('\x1b'));
+    }
+}
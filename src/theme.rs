@@ -0,0 +1,692 @@
+use std::{collections::BTreeMap, env, fs, path::PathBuf, str::FromStr};
+
+use serde::Deserialize;
+use tui::{style::Color, widgets::BorderType};
+
+use crate::config;
+
+/// directory of user themes under the config directory
+const THEME_DIR: &str = "themes";
+
+/// colours of one box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tile {
+    pub bg: Color,
+    pub fg: Color,
+}
+
+/// colours and border style of the board and the panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// boxes as `(smallest value, colours)`, sorted by value
+    pub boxes: Vec<(i32, Tile)>,
+    /// grid lines and frames, plain, rounded, double or thick
+    pub border_type: BorderType,
+    /// grid lines and modal frames
+    pub border: Color,
+    /// labels and key hints
+    pub text: Color,
+    /// score, moves and other numbers
+    pub info: Color,
+    /// merged boxes, suggested moves and replay progress
+    pub highlight: Color,
+    /// new boxes fade in through these colours, the first also dims inactive hints
+    pub fade: [Color; 2],
+    /// boxes and hints are told apart by fill pattern, text weight and brackets, not by colour
+    pub marks: bool,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        classic()
+    }
+}
+
+/// builds a built-in theme
+type Palette = fn() -> Theme;
+
+/// built-in themes by name
+pub const BUILT_IN: [(&str, Palette); 8] = [
+    ("classic", classic),
+    ("dark", dark),
+    ("solarized", solarized),
+    ("high-contrast", high_contrast),
+    ("deuteranopia", deuteranopia),
+    ("protanopia", protanopia),
+    ("monochrome", monochrome),
+    ("ansi", ansi),
+];
+
+impl Theme {
+    /// a built-in theme by name
+    pub fn named(name: &str) -> Result<Theme, String> {
+        BUILT_IN
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, theme)| theme())
+            .ok_or_else(|| {
+                let names: Vec<&str> = BUILT_IN.iter().map(|(n, _)| *n).collect();
+                format!("unknown theme `{}`, expected one of {}", name, names.join(", "))
+            })
+    }
+
+    /// colours of a box, the colours of the biggest value not above `value`
+    pub fn tile(&self, value: i32) -> Tile {
+        let fallback = Tile {
+            bg: Color::Reset,
+            fg: self.text,
+        };
+        self.boxes
+            .iter()
+            .take_while(|(from, _)| *from <= value)
+            .last()
+            .map_or(fallback, |(_, tile)| *tile)
+    }
+
+    /// same theme with every colour passed through `f`
+    fn map(&self, f: impl Fn(Color) -> Color) -> Theme {
+        Theme {
+            boxes: self
+                .boxes
+                .iter()
+                .map(|(value, tile)| {
+                    let (bg, fg) = (f(tile.bg), f(tile.fg));
+                    (*value, Tile { bg, fg })
+                })
+                .collect(),
+            border_type: self.border_type,
+            border: f(self.border),
+            text: f(self.text),
+            info: f(self.info),
+            highlight: f(self.highlight),
+            fade: self.fade.map(&f),
+            marks: self.marks,
+        }
+    }
+
+    /// same theme with every colour left to the terminal and marks instead, for `--no-color`
+    pub fn without_color(&self) -> Theme {
+        Theme {
+            marks: true,
+            ..self.map(|_| Color::Reset)
+        }
+    }
+
+    /// same theme in colours the terminal can show, others become the nearest one it can
+    pub fn with_depth(&self, depth: ColorDepth) -> Theme {
+        self.map(|color| reduce(color, depth))
+    }
+}
+
+/// a built-in theme, or a user theme in `<config dir>/tui-2048/themes/<name>.toml`
+pub fn load(name: &str) -> Result<Theme, String> {
+    let unknown = match Theme::named(name) {
+        Ok(theme) => return Ok(theme),
+        Err(unknown) => format!("{} or a theme file", unknown),
+    };
+    // names must not reach outside the themes directory
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(unknown);
+    }
+    let path = match path(name) {
+        Some(path) if path.exists() => path,
+        _ => return Err(unknown),
+    };
+
+    let content = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let invalid = |reason: String| format!("{}: invalid theme, {}", path.display(), reason);
+    let file: ThemeFile = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+    if file.name.is_some() {
+        return Err(invalid(String::from("`name`: a theme file starts from a `base` theme")));
+    }
+    let mut theme = Theme::default();
+    file.apply(&mut theme, "").map_err(invalid)?;
+
+    Ok(theme)
+}
+
+/// `<config dir>/tui-2048/themes/<name>.toml`
+pub fn path(name: &str) -> Option<PathBuf> {
+    config::dir().map(|dir| dir.join(THEME_DIR).join(format!("{}.toml", name)))
+}
+
+/// a theme file or the `[theme]` section of `config.toml`, every key is optional
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ThemeFile {
+    /// a built-in or user theme the other keys override, not in theme files
+    name: Option<String>,
+    /// a built-in theme the other keys override
+    base: Option<String>,
+    /// `plain`, `rounded`, `double` or `thick`
+    border_type: Option<String>,
+    /// colours from each value on, e.g. `64 = "magenta"` or `64 = { bg = "#f65e3b", fg = "white" }`,
+    /// other values keep their colours
+    boxes: Option<BTreeMap<String, TileColors>>,
+    border: Option<String>,
+    text: Option<String>,
+    info: Option<String>,
+    highlight: Option<String>,
+    fade: Option<[String; 2]>,
+}
+
+/// a background colour, or both colours of a box
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TileColors {
+    Bg(String),
+    Tile { bg: Option<String>, fg: Option<String> },
+}
+
+impl ThemeFile {
+    /// override colours of `theme`, errors name the key after `prefix`, e.g. `theme.`
+    pub fn apply(self, theme: &mut Theme, prefix: &str) -> Result<(), String> {
+        let err = |key: &str| {
+            let key = format!("{}{}", prefix, key);
+            move |reason: String| format!("`{}`: {}", key, reason)
+        };
+
+        if let Some(name) = self.name {
+            *theme = load(&name).map_err(err("name"))?;
+        }
+        if let Some(base) = self.base {
+            *theme = Theme::named(&base).map_err(err("base"))?;
+        }
+        if let Some(border_type) = self.border_type {
+            theme.border_type = parse_border_type(&border_type).map_err(err("border-type"))?;
+        }
+        if let Some(boxes) = self.boxes {
+            for (value, colors) in boxes {
+                let key = format!("boxes.{}", value);
+                let value: i32 = match value.parse() {
+                    Ok(value) if value >= 0 => value,
+                    _ => return Err(err(&key)(String::from("expected a box value like 64"))),
+                };
+                // a missing colour stays as the theme being overridden has it
+                let mut tile = theme.tile(value);
+                let (bg, fg) = match colors {
+                    TileColors::Bg(bg) => (Some(bg), None),
+                    TileColors::Tile { bg, fg } => (bg, fg),
+                };
+                if let Some(bg) = bg {
+                    tile.bg = parse_color(&bg).map_err(err(&key))?;
+                }
+                if let Some(fg) = fg {
+                    tile.fg = parse_color(&fg).map_err(err(&key))?;
+                }
+                theme.boxes.retain(|(from, _)| *from != value);
+                theme.boxes.push((value, tile));
+            }
+            theme.boxes.sort_by_key(|(value, _)| *value);
+        }
+
+        for (name, value, color) in [
+            ("border", self.border, &mut theme.border),
+            ("text", self.text, &mut theme.text),
+            ("info", self.info, &mut theme.info),
+            ("highlight", self.highlight, &mut theme.highlight),
+        ] {
+            if let Some(value) = value {
+                *color = parse_color(&value).map_err(err(name))?;
+            }
+        }
+        if let Some(fade) = self.fade {
+            for (color, value) in theme.fade.iter_mut().zip(fade) {
+                *color = parse_color(&value).map_err(err("fade"))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// colour names like `green` or `darkgray`, `#rrggbb`, or a 256-colour index like `208`
+pub fn parse_color(name: &str) -> Result<Color, String> {
+    if let Some(hex) = name.strip_prefix('#') {
+        return match u32::from_str_radix(hex, 16) {
+            Ok(rgb) if hex.len() == 6 => Ok(hex_color(rgb)),
+            _ => Err(format!("invalid colour `{}`, expected #rrggbb", name)),
+        };
+    }
+    if let Ok(index) = name.parse::<u8>() {
+        return Ok(Color::Indexed(index));
+    }
+
+    let color = match name.to_lowercase().replace(['-', '_', ' '], "").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return Err(format!("unknown colour `{}`", name)),
+    };
+
+    Ok(color)
+}
+
+/// `plain`, `rounded`, `double` or `thick`
+fn parse_border_type(name: &str) -> Result<BorderType, String> {
+    match name {
+        "plain" => Ok(BorderType::Plain),
+        "rounded" => Ok(BorderType::Rounded),
+        "double" => Ok(BorderType::Double),
+        "thick" => Ok(BorderType::Thick),
+        _ => Err(format!(
+            "unknown border type `{}`, expected plain, rounded, double or thick",
+            name
+        )),
+    }
+}
+
+/// colours a terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// the 16 named colours
+    Ansi16,
+    /// the xterm 256-colour palette
+    Ansi256,
+    /// 24-bit RGB
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// guess from `COLORTERM` and `TERM`
+    pub fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+impl FromStr for ColorDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorDepth, String> {
+        match s {
+            "16" => Ok(ColorDepth::Ansi16),
+            "256" => Ok(ColorDepth::Ansi256),
+            "truecolor" => Ok(ColorDepth::TrueColor),
+            _ => Err(format!(
+                "invalid colour depth `{}`, expected `auto`, `16`, `256` or `truecolor`",
+                s
+            )),
+        }
+    }
+}
+
+/// the 16 named colours and their usual xterm values
+const ANSI: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// channel levels of the 6x6x6 colour cube in the 256-colour palette, from index 16
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// nearest colour `depth` can show, named colours are left alone
+fn reduce(color: Color, depth: ColorDepth) -> Color {
+    let rgb = match (color, depth) {
+        (_, ColorDepth::TrueColor) | (Color::Indexed(_), ColorDepth::Ansi256) => return color,
+        (Color::Rgb(r, g, b), _) => (r, g, b),
+        (Color::Indexed(i), _) => indexed_rgb(i),
+        _ => return color,
+    };
+
+    match depth {
+        ColorDepth::Ansi256 => {
+            let levels = || CUBE.iter().enumerate().map(|(i, l)| (i as u8, (*l, *l, *l)));
+            let level = |c: u8| nearest(levels(), (c, c, c));
+            let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+            let cube = 16 + 36 * r + 6 * g + b;
+            let grays = (0..24).map(|i| (232 + i, (8 + 10 * i, 8 + 10 * i, 8 + 10 * i)));
+            let candidates = grays.chain([(cube, indexed_rgb(cube))]);
+            Color::Indexed(nearest(candidates, rgb))
+        }
+        _ => nearest(ANSI.iter().copied(), rgb),
+    }
+}
+
+/// RGB of a 256-colour palette index
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI[index as usize].1,
+        16..=231 => {
+            let i = (index - 16) as usize;
+            (CUBE[i / 36], CUBE[i / 6 % 6], CUBE[i % 6])
+        }
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        }
+    }
+}
+
+/// the candidate closest to `rgb`
+fn nearest<T>(candidates: impl Iterator<Item = (T, (u8, u8, u8))>, rgb: (u8, u8, u8)) -> T {
+    let distance = |(r, g, b): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+    };
+    candidates
+        .min_by_key(|(_, c)| distance(*c))
+        .map(|(t, _)| t)
+        .expect("no colour to pick from")
+}
+
+fn hex_color(rgb: u32) -> Color {
+    Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+/// boxes from `(value, background, foreground)` as `0xrrggbb`
+fn hex_tiles(tiles: &[(i32, u32, u32)]) -> Vec<(i32, Tile)> {
+    tiles
+        .iter()
+        .map(|(value, bg, fg)| {
+            let (bg, fg) = (hex_color(*bg), hex_color(*fg));
+            (*value, Tile { bg, fg })
+        })
+        .collect()
+}
+
+/// the colours of the original web game
+fn classic() -> Theme {
+    let (dark, light) = (0x776e65, 0xf9f6f2);
+    Theme {
+        boxes: hex_tiles(&[
+            (0, 0xeee4da, dark),
+            (4, 0xede0c8, dark),
+            (8, 0xf2b179, light),
+            (16, 0xf59563, light),
+            (32, 0xf67c5f, light),
+            (64, 0xf65e3b, light),
+            (128, 0xedcf72, light),
+            (256, 0xedcc61, light),
+            (512, 0xedc850, light),
+            (1024, 0xedc53f, light),
+            (2048, 0xedc22e, light),
+            (4096, 0x3c3a32, light),
+        ]),
+        border_type: BorderType::Rounded,
+        border: hex_color(0xbbada0),
+        text: hex_color(0xeee4da),
+        info: hex_color(0xedc22e),
+        highlight: hex_color(0xf65e3b),
+        fade: [hex_color(0xcdc1b4), hex_color(0xe0d6c8)],
+        marks: false,
+    }
+}
+
+/// muted boxes for dark terminals
+fn dark() -> Theme {
+    let (dark, light) = (0x1e2127, 0xdfe4ea);
+    Theme {
+        boxes: hex_tiles(&[
+            (0, 0x3d4454, light),
+            (4, 0x4b5467, light),
+            (8, 0x8e5b2c, light),
+            (16, 0xa4542b, light),
+            (32, 0xb8452f, light),
+            (64, 0xc0392b, light),
+            (128, 0x8a7a2e, light),
+            (256, 0x9c8a2a, light),
+            (512, 0xae9a24, dark),
+            (1024, 0xc0aa1d, dark),
+            (2048, 0xd4b916, dark),
+            (4096, 0x6c3483, light),
+        ]),
+        border_type: BorderType::Plain,
+        border: hex_color(0x57606f),
+        text: hex_color(0xa4b0be),
+        info: hex_color(0xeccc68),
+        highlight: hex_color(0xff6b81),
+        fade: [hex_color(0x2f3542), hex_color(0x57606f)],
+        marks: false,
+    }
+}
+
+/// the solarized palette
+fn solarized() -> Theme {
+    let (base03, base1, base3) = (0x002b36, 0x93a1a1, 0xfdf6e3);
+    Theme {
+        boxes: hex_tiles(&[
+            (0, 0x073642, base1),
+            (4, 0x586e75, base3),
+            (8, 0xb58900, base3),
+            (16, 0xcb4b16, base3),
+            (32, 0xdc322f, base3),
+            (64, 0xd33682, base3),
+            (128, 0x6c71c4, base3),
+            (256, 0x268bd2, base3),
+            (512, 0x2aa198, base3),
+            (1024, 0x859900, base3),
+            (2048, 0xeee8d5, base03),
+            (4096, base03, base3),
+        ]),
+        border_type: BorderType::Plain,
+        border: hex_color(0x586e75),
+        text: hex_color(0x839496),
+        info: hex_color(0xb58900),
+        highlight: hex_color(0xcb4b16),
+        fade: [hex_color(0x073642), hex_color(0x586e75)],
+        marks: false,
+    }
+}
+
+/// bright named colours, black or white numbers and thick lines
+fn high_contrast() -> Theme {
+    let tile = |bg, fg| Tile { bg, fg };
+    Theme {
+        boxes: vec![
+            (0, tile(Color::White, Color::Black)),
+            (4, tile(Color::LightYellow, Color::Black)),
+            (8, tile(Color::Yellow, Color::Black)),
+            (16, tile(Color::LightRed, Color::Black)),
+            (32, tile(Color::Red, Color::White)),
+            (64, tile(Color::LightMagenta, Color::Black)),
+            (128, tile(Color::Magenta, Color::White)),
+            (256, tile(Color::LightCyan, Color::Black)),
+            (512, tile(Color::Cyan, Color::Black)),
+            (1024, tile(Color::LightBlue, Color::Black)),
+            (2048, tile(Color::Blue, Color::White)),
+            (4096, tile(Color::Black, Color::White)),
+        ],
+        border_type: BorderType::Thick,
+        border: Color::White,
+        text: Color::White,
+        info: Color::LightYellow,
+        highlight: Color::LightCyan,
+        fade: [Color::DarkGray, Color::Gray],
+        marks: false,
+    }
+}
+
+/// blues then oranges, lighter to darker, no red against green
+fn deuteranopia() -> Theme {
+    let (dark, light) = (0x1b1b1b, 0xffffff);
+    Theme {
+        boxes: hex_tiles(&[
+            (0, 0xe8eef7, dark),
+            (4, 0xc6dbef, dark),
+            (8, 0x9ecae1, dark),
+            (16, 0x6baed6, dark),
+            (32, 0x3182bd, light),
+            (64, 0x08519c, light),
+            (128, 0xfdd49e, dark),
+            (256, 0xfdae6b, dark),
+            (512, 0xfd8d3c, dark),
+            (1024, 0xe6550d, light),
+            (2048, 0xa63603, light),
+            (4096, 0x000000, light),
+        ]),
+        border_type: BorderType::Rounded,
+        border: hex_color(0x8c8c8c),
+        text: hex_color(0xd9d9d9),
+        info: hex_color(0x56b4e9),
+        highlight: hex_color(0xe69f00),
+        fade: [hex_color(0x404040), hex_color(0x737373)],
+        marks: false,
+    }
+}
+
+/// blues then yellows, reds look dark with protanopia so there are none
+fn protanopia() -> Theme {
+    let (dark, light) = (0x1b1b1b, 0xffffff);
+    Theme {
+        boxes: hex_tiles(&[
+            (0, 0xf2f2e6, dark),
+            (4, 0xe3e1c9, dark),
+            (8, 0xa6bddb, dark),
+            (16, 0x74a9cf, dark),
+            (32, 0x3690c0, light),
+            (64, 0x0570b0, light),
+            (128, 0x034e7b, light),
+            (256, 0xfff7bc, dark),
+            (512, 0xfee391, dark),
+            (1024, 0xfec44f, dark),
+            (2048, 0xf5c400, dark),
+            (4096, 0x00224e, light),
+        ]),
+        border_type: BorderType::Rounded,
+        border: hex_color(0x8c8c8c),
+        text: hex_color(0xd9d9d9),
+        info: hex_color(0x56b4e9),
+        highlight: hex_color(0xfee838),
+        fade: [hex_color(0x404040), hex_color(0x737373)],
+        marks: false,
+    }
+}
+
+/// shades of gray, darker for bigger boxes
+fn monochrome() -> Theme {
+    let (black, white) = (0x000000, 0xffffff);
+    Theme {
+        boxes: hex_tiles(&[
+            (0, 0xf0f0f0, black),
+            (4, 0xd8d8d8, black),
+            (8, 0xc0c0c0, black),
+            (16, 0xa8a8a8, black),
+            (32, 0x909090, black),
+            (64, 0x787878, white),
+            (128, 0x606060, white),
+            (256, 0x484848, white),
+            (512, 0x303030, white),
+            (1024, 0x202020, white),
+            (2048, 0x101010, white),
+            (4096, 0x000000, white),
+        ]),
+        border_type: BorderType::Plain,
+        border: hex_color(0x808080),
+        text: hex_color(0xc0c0c0),
+        info: hex_color(0xffffff),
+        highlight: hex_color(0xffffff),
+        fade: [hex_color(0x303030), hex_color(0x606060)],
+        marks: false,
+    }
+}
+
+/// the first colours of this game, named colours only
+fn ansi() -> Theme {
+    let tile = |bg| Tile {
+        bg,
+        fg: Color::Black,
+    };
+    Theme {
+        boxes: vec![
+            (0, tile(Color::Green)),
+            (64, tile(Color::Magenta)),
+            (256, tile(Color::Cyan)),
+            (1024, tile(Color::LightRed)),
+            (4096, tile(Color::Red)),
+        ],
+        border_type: BorderType::Plain,
+        border: Color::Green,
+        text: Color::Blue,
+        info: Color::Green,
+        highlight: Color::Yellow,
+        fade: [Color::DarkGray, Color::Gray],
+        marks: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_reduce_to_the_terminal_depth() {
+        let classic = classic();
+        assert_eq!(classic.with_depth(ColorDepth::TrueColor), classic);
+
+        let reduced = classic.with_depth(ColorDepth::Ansi256);
+        assert!(reduced.boxes.iter().all(|(_, tile)| matches!(tile.bg, Color::Indexed(_))));
+        // #f65e3b is closest to cube colour (255, 95, 95)
+        assert_eq!(reduced.tile(64).bg, Color::Indexed(203));
+
+        let reduced = classic.with_depth(ColorDepth::Ansi16);
+        assert_eq!(reduced.tile(2048).bg, Color::Yellow);
+        assert_eq!(high_contrast().with_depth(ColorDepth::Ansi16), high_contrast());
+    }
+
+    #[test]
+    fn theme_files_override_their_base() {
+        let file: ThemeFile = toml::from_str(
+            r##"
+            base = "dark"
+            border-type = "double"
+            text = "#102030"
+
+            [boxes]
+            0 = "blue"
+            64 = { fg = "208" }
+            "##,
+        )
+        .unwrap();
+        let mut theme = Theme::default();
+        file.apply(&mut theme, "").unwrap();
+
+        let dark = dark();
+        assert_eq!(theme.border_type, BorderType::Double);
+        assert_eq!(theme.text, Color::Rgb(0x10, 0x20, 0x30));
+        assert_eq!(theme.tile(2).bg, Color::Blue);
+        assert_eq!(theme.tile(2).fg, dark.tile(2).fg);
+        assert_eq!(theme.tile(64).bg, dark.tile(64).bg);
+        assert_eq!(theme.tile(64).fg, Color::Indexed(208));
+        assert_eq!(theme.tile(128), dark.tile(128));
+
+        let file: ThemeFile = toml::from_str("border = \"purple\"").unwrap();
+        let err = file.apply(&mut theme, "theme.").unwrap_err();
+        assert_eq!(err, "`theme.border`: unknown colour `purple`");
+    }
+}
@@ -0,0 +1,386 @@
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{BarChart, Block, Borders, Paragraph, Row, Table},
+    Frame,
+};
+
+use tui_2048::game::Command;
+
+use crate::app::{App, Screen};
+use crate::board::Board;
+use crate::config::{self, Action, Keys};
+use crate::records::{Record, Records};
+use crate::theme::Theme;
+use crate::utils;
+
+/// one frame, the board or statistics on the left and the panel on the right
+///
+/// every label is owned by the frame's widgets and dropped with them
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &App, theme: &Theme, keys: &Keys, ascii: bool) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(80), Constraint::Percentage(20)].as_ref())
+        .split(f.size());
+    // Game board
+    let title = match (app.get_notice(), app.is_replay()) {
+        (Some(notice), _) => format!("2048-@wander | {}", notice),
+        (None, true) => String::from("2048-@wander | replay"),
+        (None, false) => String::from("2048-@wander"),
+    };
+    match app.get_screen() {
+        Screen::Game => {
+            let grid = app.get_grid();
+            let modal = modal_text(app, theme, keys);
+            let board = Board::new(&grid, theme)
+                .animation(app.get_animation())
+                .modal(modal.as_ref().map(|(m, c, color)| (*m, c.as_str(), *color)))
+                .ascii(ascii)
+                .block(frame(title, theme, ascii));
+            f.render_widget(board, chunks[0]);
+        }
+        Screen::Stats => {
+            draw_stats(f, chunks[0], app.get_records(), theme, keys, ascii)
+        }
+    }
+    draw_panel(f, chunks[1], app, theme, keys, ascii);
+}
+
+/// numbers at the top, controls at the bottom
+fn draw_panel<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    app: &App,
+    theme: &Theme,
+    keys: &Keys,
+    ascii: bool,
+) {
+    let label = |action| keys.label(action);
+    let text = Style::default().fg(theme.text);
+    let info = Style::default().fg(theme.info);
+
+    let block = frame(format!("Panel | {}", app.get_spawn_mode()), theme, ascii);
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let mut stats = vec![Spans::from(Span::styled("> Relax <", text)), Spans::default()];
+    for stat in [
+        format!("Score: {}", app.get_score()),
+        format!("Tiles: {}", app.get_tile_sum()),
+        format!("Moves: {}", app.get_moves()),
+        format!("Seed:  {}", app.get_seed()),
+        format!("Undos: {}", app.get_undos()),
+    ] {
+        stats.push(Spans::from(Span::styled(stat, info)));
+        stats.push(Spans::default());
+    }
+
+    let controls = if let Some((position, steps, speed, paused)) = app.get_playback() {
+        let progress = format!(
+            "Replay {}/{} x{}{}",
+            position,
+            steps,
+            speed,
+            if paused { " paused" } else { "" }
+        );
+        vec![
+            Spans::from(Span::styled(progress, Style::default().fg(theme.highlight))),
+            Spans::from(Span::styled("Play/Pause[Space]", text)),
+            Spans::from(Span::styled(
+                format!("Step[{}/{}]", label(Action::Left), label(Action::Right)),
+                text,
+            )),
+            Spans::from(Span::styled("Speed[+/-]", text)),
+            Spans::from(Span::styled(format!("Quit[{}]", label(Action::Quit)), text)),
+        ]
+    } else {
+        let mut hint = vec![Span::styled(format!("Hint[{}]", label(Action::Hint)), text)];
+        for (cmd, key) in [
+            (Command::Left, Key::Left),
+            (Command::Up, Key::Up),
+            (Command::Right, Key::Right),
+            (Command::Down, Key::Down),
+        ] {
+            let style = match (app.get_hint() == Some(cmd), theme.marks) {
+                (true, false) => Style::default().fg(theme.highlight),
+                (true, true) => Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                (false, _) => Style::default().fg(theme.fade[0]),
+            };
+            hint.push(Span::raw(" "));
+            hint.push(Span::styled(config::arrow(key, ascii), style));
+        }
+        let autoplay = format!(
+            "Autoplay[{}]: {}",
+            label(Action::Autoplay),
+            if app.is_autoplay() { "on" } else { "off" }
+        );
+        let (undo, redo) = (label(Action::Undo), label(Action::Redo));
+        vec![
+            Spans::from(hint),
+            Spans::from(Span::styled(autoplay, text)),
+            Spans::from(Span::styled(format!("Stats[{}]", label(Action::Stats)), text)),
+            Spans::from(Span::styled(format!("Undo[{}] Redo[{}]", undo, redo), text)),
+            Spans::from(Span::styled(format!("Quit[{}]", label(Action::Quit)), text)),
+        ]
+    };
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(controls.len() as u16)].as_ref())
+        .split(inner);
+    f.render_widget(Paragraph::new(stats), chunks[0]);
+    f.render_widget(Paragraph::new(controls), chunks[1]);
+}
+
+/// high-score table and lifetime statistics
+fn draw_stats<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    records: &Records,
+    theme: &Theme,
+    keys: &Keys,
+    ascii: bool,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(4),
+                Constraint::Min(6),
+                Constraint::Length(10),
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let best = records.high_scores.first().map_or(0, |r| r.score);
+    let summary = Paragraph::new(vec![
+        Spans::from(format!(
+            "Games played: {}    Won: {} ({:.1}%)    Best score: {}",
+            records.games_played,
+            records.games_won,
+            records.win_rate(),
+            best
+        )),
+        Spans::from(Span::styled(
+            format!("* used undo    Back[{}]", keys.label(Action::Stats)),
+            Style::default().fg(theme.text),
+        )),
+    ])
+    .block(frame(String::from("Statistics"), theme, ascii));
+    f.render_widget(summary, chunks[0]);
+
+    let rows = records
+        .high_scores
+        .iter()
+        .enumerate()
+        .map(|(i, r)| Row::new(record_row(i, r)));
+    let widths = [
+        Constraint::Length(3),
+        Constraint::Length(9),
+        Constraint::Length(7),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(7),
+        Constraint::Length(9),
+        Constraint::Length(10),
+    ];
+    let table = Table::new(rows)
+        .header(
+            Row::new(RECORD_HEADER.to_vec())
+                .style(Style::default().add_modifier(Modifier::BOLD)),
+        )
+        .widths(&widths)
+        .block(frame(String::from("High scores"), theme, ascii));
+    f.render_widget(table, chunks[1]);
+
+    let labels: Vec<String> = records.max_boxes.keys().map(|k| k.to_string()).collect();
+    let data: Vec<(&str, u64)> = labels
+        .iter()
+        .zip(records.max_boxes.values())
+        .map(|(label, count)| (label.as_str(), *count as u64))
+        .collect();
+    let chart = BarChart::default()
+        .data(&data)
+        .bar_width(6)
+        .bar_set(if ascii { ASCII_BARS } else { symbols::bar::NINE_LEVELS })
+        .bar_style(Style::default().fg(theme.info))
+        .block(frame(String::from("Highest boxes"), theme, ascii));
+    f.render_widget(chart, chunks[2]);
+}
+
+/// columns of the high-score table
+pub const RECORD_HEADER: [&str; 8] = ["#", "Score", "Max", "Moves", "Time", "Board", "Rule", "Date"];
+
+/// a high-score table row, `i` counts from 0
+pub fn record_row(i: usize, r: &Record) -> Vec<String> {
+    vec![
+        format!("{}", i + 1),
+        format!("{}{}", r.score, if r.undos > 0 { "*" } else { "" }),
+        r.max_box.to_string(),
+        r.moves.to_string(),
+        utils::format_duration(r.duration),
+        format!("{}x{}", r.rows, r.cols),
+        r.rule.to_string(),
+        utils::format_date(r.date),
+    ]
+}
+
+/// bars of the `--ascii` statistics
+const ASCII_BARS: symbols::bar::Set = symbols::bar::Set {
+    full: "#",
+    seven_eighths: "#",
+    three_quarters: "#",
+    five_eighths: "=",
+    half: "=",
+    three_eighths: "=",
+    one_quarter: "-",
+    one_eighth: "-",
+    empty: " ",
+};
+
+/// titled block, its borders are box-drawing characters so `--ascii` leaves them out
+fn frame(title: String, theme: &Theme, ascii: bool) -> Block<'static> {
+    let borders = if ascii { Borders::NONE } else { Borders::ALL };
+    Block::default()
+        .borders(borders)
+        .border_type(theme.border_type)
+        .border_style(Style::default().fg(theme.border))
+        .title(title)
+}
+
+/// message, choices and frame colour shown over the board, e.g. `GAME OVER!` and
+/// `Restart[R] Quit[Q]`
+fn modal_text(app: &App, theme: &Theme, keys: &Keys) -> Option<(&'static str, String, Color)> {
+    let label = |action| keys.label(action);
+    if app.is_resume_pending() {
+        let choices = format!("Yes[{}] No[{}]", label(Action::Resume), label(Action::Discard));
+        Some(("Resume last game?", choices, theme.border))
+    } else if !app.is_alive() {
+        let choices = if app.is_replay() {
+            format!("Back[{}] Quit[{}]", label(Action::Left), label(Action::Quit))
+        } else {
+            format!("Restart[{}] Quit[{}]", label(Action::Restart), label(Action::Quit))
+        };
+        Some(("GAME OVER!", choices, theme.border))
+    } else if app.is_won() && !app.is_replay() {
+        let choices = format!(
+            "Keep going[{}] New game[{}]",
+            label(Action::KeepGoing),
+            label(Action::Restart)
+        );
+        Some(("YOU WIN!", choices, theme.highlight))
+    } else {
+        None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::records::Record;
+    use std::alloc::{GlobalAlloc, Layout as AllocLayout, System};
+    use std::cell::Cell;
+    use tui::{backend::TestBackend, Terminal};
+    use tui_2048::game::{Config, Game, Rule};
+
+    thread_local! {
+        /// count allocations of this thread, other tests run in parallel
+        static COUNTING: Cell<bool> = const { Cell::new(false) };
+        /// bytes allocated and not freed yet by this thread while counting
+        static LIVE: Cell<isize> = const { Cell::new(0) };
+    }
+
+    /// add `bytes` to this thread's count if it is counting
+    fn count(bytes: isize) {
+        // thread locals may be gone while a thread exits
+        let _ = COUNTING.try_with(|counting| {
+            if counting.get() {
+                let _ = LIVE.try_with(|live| live.set(live.get() + bytes));
+            }
+        });
+    }
+
+    struct Counting;
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: AllocLayout) -> *mut u8 {
+            count(layout.size() as isize);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: AllocLayout) {
+            count(-(layout.size() as isize));
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: Counting = Counting;
+
+    fn records() -> Records {
+        let mut records = Records::default();
+        for score in [20480, 3072, 512] {
+            records.add(Record {
+                score,
+                max_box: 2048,
+                moves: 900,
+                duration: 600,
+                rows: 4,
+                cols: 4,
+                rule: Rule::Standard,
+                date: 1_700_000_000,
+                undos: 0,
+                won: score > 10000,
+            });
+        }
+        records
+    }
+
+    /// labels are owned by the frame, hundreds of frames over every screen keep memory flat
+    #[test]
+    fn memory_stays_flat_over_frames() {
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        let (theme, keys) = (Theme::default(), Keys::default());
+
+        let mut game = Game::from_seed(Config::default(), 7);
+        game.start();
+        for cmd in Command::MOVES.iter().cycle().take(40) {
+            game.next_tick(*cmd);
+        }
+        let mut replay = App::new(Config::default(), None);
+        replay.watch(game.get_replay()).unwrap();
+
+        let mut playing = App::new(Config::default(), Some(2048));
+        playing.set_records(records());
+        playing.hint();
+        let mut stats = App::new(Config::default(), Some(2048));
+        stats.set_records(records());
+        stats.toggle_stats();
+
+        for app in [&playing, &stats, &replay] {
+            for ascii in [false, true] {
+                let mut draw_frames = |n| {
+                    for _ in 0..n {
+                        terminal
+                            .draw(|f| draw(f, app, &theme, &keys, ascii))
+                            .unwrap();
+                    }
+                };
+                draw_frames(20);
+                LIVE.with(|live| live.set(0));
+                COUNTING.with(|counting| counting.set(true));
+                draw_frames(200);
+                COUNTING.with(|counting| counting.set(false));
+                let kept = LIVE.with(Cell::get);
+
+                assert_eq!(kept, 0, "{} bytes kept by 200 frames", kept);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+//! Small helpers for formatting and parsing.
+
 /// predict if two matrices contains same value
 pub fn equal_slice<T>(a: &[T], b: &[T]) -> bool where T: Eq {
   if a.len() != b.len() {
@@ -12,4 +14,103 @@ pub fn equal_slice<T>(a: &[T], b: &[T]) -> bool where T: Eq {
 
   true
   
-}
\ No newline at end of file
+}
+
+/// format unix seconds as `YYYY-MM-DD` (UTC)
+pub fn format_date(secs: u64) -> String {
+  // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+  let z = (secs / 86400) as i64 + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// format seconds as `1:02:03` or `02:03`
+pub fn format_duration(secs: u64) -> String {
+  let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+  if h > 0 {
+    format!("{}:{:02}:{:02}", h, m, s)
+  } else {
+    format!("{:02}:{:02}", m, s)
+  }
+}
+
+/// smallest and largest side of the board a new game may have
+pub const MIN_SIDE: usize = 2;
+/// see `MIN_SIDE`
+pub const MAX_SIDE: usize = 16;
+
+/// `4x6` -> (4, 6), each side between `min` and `max`
+pub fn parse_size(value: &str, min: usize, max: usize) -> Result<(usize, usize), String> {
+  let invalid = || format!("invalid size `{}`, expected <rows>x<cols> between {} and {}", value, min, max);
+
+  let (rows, cols) = value.split_once('x').ok_or_else(invalid)?;
+  let rows: usize = rows.trim().parse().map_err(|_| invalid())?;
+  let cols: usize = cols.trim().parse().map_err(|_| invalid())?;
+
+  let range = min..=max;
+  if !range.contains(&rows) || !range.contains(&cols) {
+    return Err(invalid());
+  }
+
+  Ok((rows, cols))
+}
+
+/// a power of two, at least `4`
+pub fn check_target(target: i32) -> Result<i32, String> {
+  if target >= 4 && target.count_ones() == 1 {
+    Ok(target)
+  } else {
+    Err(format!("invalid target `{}`, expected a power of two like 2048", target))
+  }
+}
+
+/// `2048` -> 2048, a power of two, at least `4`
+pub fn parse_target(value: &str) -> Result<i32, String> {
+  value
+    .parse::<i32>()
+    .map_err(|_| format!("invalid target `{}`, expected a power of two like 2048", value))
+    .and_then(check_target)
+}
+
+/// `9,1` -> [9, 1, 0, 0], weights of `2`, `4`, `8` and `16`, missing ones are `0`
+pub fn parse_weights(value: &str) -> Result<[u32; 4], String> {
+  let invalid = || format!("invalid spawn weights `{}`, expected up to 4 numbers like 9,1", value);
+
+  let mut weights = [0; 4];
+  let parts: Vec<&str> = value.split(',').collect();
+  if parts.len() > weights.len() {
+    return Err(invalid());
+  }
+  for (weight, part) in weights.iter_mut().zip(parts) {
+    *weight = part.trim().parse().map_err(|_| invalid())?;
+  }
+
+  Ok(weights)
+}
+
+/// rows of a board as text, numbers right aligned, empty cells are `.`
+pub fn format_grid(grid: &[Vec<i32>]) -> Vec<String> {
+  let width = grid.iter().flatten().map(|x| x.to_string().len()).max().unwrap_or(1);
+
+  grid
+    .iter()
+    .map(|row| {
+      row
+        .iter()
+        .map(|x| match x {
+          0 => format!("{:>width$}", ".", width = width),
+          x => format!("{:>width$}", x, width = width),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+    })
+    .collect()
+}